@@ -0,0 +1,203 @@
+use num_bigint::{BigUint, RandBigInt};
+use rand::Rng;
+
+use crate::adapters::Adapters;
+
+/// Every valid chain from the outlet to the device, counted without listing
+/// them, in lexicographic order of joltages
+#[derive(Debug)]
+pub struct Arrangements<'a> {
+    adapters: &'a Adapters,
+    /// Chains from each adapter to the device
+    completions: Vec<BigUint>,
+}
+
+impl<'a> Arrangements<'a> {
+    pub fn new(adapters: &'a Adapters) -> Self {
+        let len = adapters.joltages().len();
+        let mut completions = vec![BigUint::default(); len];
+        completions[len - 1] = 1u32.into();
+        for idx in (0..len - 1).rev() {
+            completions[idx] = adapters
+                .successors(idx)
+                .map(|next| &completions[next])
+                .sum();
+        }
+        Arrangements {
+            adapters,
+            completions,
+        }
+    }
+
+    pub fn count(&self) -> &BigUint {
+        &self.completions[0]
+    }
+
+    /// Lazily walks every chain, in order
+    pub fn chains(&self) -> Chains<'_, 'a> {
+        Chains {
+            arrangements: self,
+            path: Vec::new(),
+            started: false,
+        }
+    }
+
+    /// The chain at index `k` in order, or `None` past the end
+    pub fn nth(&self, k: &BigUint) -> Option<Vec<u32>> {
+        if k >= self.count() {
+            return None;
+        }
+        let mut k = k.clone();
+        let mut path = vec![0];
+        while let Some(&cur) = path.last().filter(|cur| !self.is_device(**cur)) {
+            // Skip whole subtrees until `k` falls inside one
+            for next in self.adapters.successors(cur) {
+                if k < self.completions[next] {
+                    path.push(next);
+                    break;
+                }
+                k -= &self.completions[next];
+            }
+        }
+        Some(self.joltages(&path))
+    }
+
+    /// A chain drawn uniformly at random, or `None` if there are none
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<Vec<u32>> {
+        if self.count() == &BigUint::default() {
+            return None;
+        }
+        self.nth(&rng.gen_biguint_below(self.count()))
+    }
+
+    fn is_device(&self, idx: usize) -> bool {
+        idx == self.completions.len() - 1
+    }
+
+    fn first_live_successor(&self, idx: usize, after: Option<usize>) -> Option<usize> {
+        self.adapters
+            .successors(idx)
+            .filter(|next| after.is_none_or(|after| *next > after))
+            .find(|next| self.completions[*next] > BigUint::default())
+    }
+
+    fn joltages(&self, path: &[usize]) -> Vec<u32> {
+        path.iter()
+            .map(|idx| self.adapters.joltages()[*idx])
+            .collect()
+    }
+}
+
+/// Iterator over every chain, from `Arrangements::chains`. Dead ends are
+/// skipped using the counts, so each step does work proportional to the
+/// chain length.
+#[derive(Debug)]
+pub struct Chains<'s, 'a> {
+    arrangements: &'s Arrangements<'a>,
+    /// Indexes of the adapters in the current chain
+    path: Vec<usize>,
+    started: bool,
+}
+
+impl Iterator for Chains<'_, '_> {
+    type Item = Vec<u32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let arrangements = self.arrangements;
+        if !self.started {
+            self.started = true;
+            if arrangements.completions[0] == BigUint::default() {
+                return None;
+            }
+            self.path.push(0);
+        } else {
+            // Back up to the last adapter with another way forward
+            loop {
+                let last = self.path.pop()?;
+                let parent = *self.path.last()?;
+                if let Some(next) = arrangements.first_live_successor(parent, Some(last)) {
+                    self.path.push(next);
+                    break;
+                }
+            }
+        }
+
+        while let Some(&cur) = self
+            .path
+            .last()
+            .filter(|cur| !arrangements.is_device(**cur))
+        {
+            self.path
+                .push(arrangements.first_live_successor(cur, None).unwrap());
+        }
+        Some(arrangements.joltages(&self.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::adapters::Tolerance;
+
+    fn small() -> Adapters {
+        Adapters::from_file("test-input-small", Tolerance::default()).unwrap()
+    }
+
+    #[test]
+    fn all_chains() {
+        let adapters = small();
+        let arrangements = adapters.arrangements();
+        let chains: Vec<_> = arrangements.chains().collect();
+        assert_eq!(chains.len(), 8);
+        assert!(chains.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(chains[0], [0, 1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19, 22]);
+        assert_eq!(chains[7], [0, 1, 4, 7, 10, 12, 15, 16, 19, 22]);
+
+        for (k, chain) in chains.iter().enumerate() {
+            assert_eq!(arrangements.nth(&BigUint::from(k)).as_ref(), Some(chain));
+        }
+        assert_eq!(arrangements.nth(&8u32.into()), None);
+    }
+
+    #[test]
+    fn dead_ends() {
+        // 3 and 5 can't reach 9, and nothing reaches the device past 9
+        let adapters = Adapters::new([2, 3, 5, 6, 9], Tolerance::new(vec![3], 3).unwrap());
+        let chains: Vec<_> = adapters.arrangements().chains().collect();
+        assert_eq!(chains, [vec![0, 3, 6, 9, 12]]);
+
+        let stuck = Adapters::new([5], Tolerance::default());
+        assert_eq!(stuck.arrangements().chains().next(), None);
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(stuck.arrangements().sample(&mut rng), None);
+    }
+
+    #[test]
+    fn uniform_samples() {
+        let adapters = small();
+        let arrangements = adapters.arrangements();
+        let mut rng = StdRng::seed_from_u64(10);
+        let mut seen: HashMap<Vec<u32>, usize> = HashMap::new();
+        for _ in 0..8000 {
+            *seen
+                .entry(arrangements.sample(&mut rng).unwrap())
+                .or_default() += 1;
+        }
+        assert_eq!(seen.len(), 8);
+        assert!(seen.values().all(|count| (850..1150).contains(count)));
+    }
+
+    #[test]
+    fn last_index() {
+        let adapters = Adapters::from_file("test-input", Tolerance::default()).unwrap();
+        let arrangements = adapters.arrangements();
+        let last = arrangements.count() - 1u32;
+        let chains: Vec<_> = arrangements.chains().collect();
+        assert_eq!(chains.len(), 19208);
+        assert_eq!(arrangements.nth(&last).as_ref(), chains.last());
+    }
+}
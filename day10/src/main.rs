@@ -1,35 +1,150 @@
+use std::env;
+
 use adapters::*;
+use anyhow::{anyhow, bail};
+use itertools::Itertools;
+
+mod arrangements;
+mod validate;
+
+const USAGE: &str =
+    "Usage: day10 [tolerance <gaps> <device offset>] [first <n>|sample <n>|nth <k>]";
+
+/// Usage: `day10 [tolerance <gaps> <device offset>] [first <n>|sample <n>|nth <k>]`
+///
+/// - `tolerance 1,2,3 3` is the default
+/// - `first` lists the first `n` chains in order
+/// - `sample` draws `n` chains uniformly at random
+/// - `nth` prints the chain at index `k`, counting from 0
+fn main() -> anyhow::Result<()> {
+    let args: Vec<_> = env::args().skip(1).collect();
+    let args: Vec<_> = args.iter().map(String::as_str).collect();
+    let (tolerance, command) = match args[..] {
+        ["tolerance", gaps, offset, ref command @ ..] => (
+            Tolerance::new(
+                gaps.split(',')
+                    .map(str::parse)
+                    .collect::<Result<Vec<_>, _>>()?,
+                offset.parse()?,
+            )?,
+            command,
+        ),
+        ["tolerance", ..] => bail!(USAGE),
+        ref command => (Tolerance::default(), command),
+    };
 
-fn main() {
-    let listing = Adapters::from_file("input").unwrap();
-    println!("{:#?}", listing.differences().product());
-    println!("{}", listing.num_arrangements());
+    let listing = Adapters::from_file("input", tolerance)?;
+    listing.validate()?;
+    let arrangements = listing.arrangements();
+    let show = |chain: Vec<u32>| chain.iter().join(" ");
+    match command {
+        [] => {
+            println!("{:#?}", listing.differences().product());
+            println!("{}", listing.num_arrangements());
+        }
+        ["first", n] => {
+            for chain in arrangements.chains().take(n.parse()?) {
+                println!("{}", show(chain));
+            }
+        }
+        ["sample", n] => {
+            let mut rng = rand::thread_rng();
+            for _ in 0..n.parse()? {
+                let chain = arrangements
+                    .sample(&mut rng)
+                    .ok_or_else(|| anyhow!("There are no valid chains"))?;
+                println!("{}", show(chain));
+            }
+        }
+        ["nth", k] => {
+            let chain = arrangements
+                .nth(&k.parse()?)
+                .ok_or_else(|| anyhow!("There are only {} chains", arrangements.count()))?;
+            println!("{}", show(chain));
+        }
+        _ => bail!(USAGE),
+    }
+    Ok(())
 }
 
 mod adapters {
     use std::{
+        collections::BTreeMap,
         fs::File,
-        io::{BufRead, BufReader},
+        io::{self, BufRead, BufReader},
         iter,
     };
 
+    use anyhow::bail;
     use itertools::Itertools;
-    use std::collections::HashMap;
+    use num_bigint::BigUint;
+
+    use crate::{arrangements::Arrangements, validate::AdapterError};
+
+    /// Which joltage steps an adapter accepts, and how far above the highest
+    /// adapter the device sits
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Tolerance {
+        gaps: Vec<u32>,
+        device_offset: u32,
+    }
+
+    impl Tolerance {
+        pub fn new(mut gaps: Vec<u32>, device_offset: u32) -> anyhow::Result<Self> {
+            gaps.sort_unstable();
+            gaps.dedup();
+            if gaps.is_empty() || gaps[0] == 0 {
+                bail!("Gaps must be positive, and there must be at least one");
+            }
+            Ok(Tolerance {
+                gaps,
+                device_offset,
+            })
+        }
+
+        /// Sorted, without repeats
+        pub fn gaps(&self) -> &[u32] {
+            &self.gaps
+        }
+
+        pub fn allows(&self, gap: u32) -> bool {
+            self.gaps.binary_search(&gap).is_ok()
+        }
+
+        pub fn max_gap(&self) -> u32 {
+            *self.gaps.last().unwrap()
+        }
+    }
+
+    impl Default for Tolerance {
+        fn default() -> Self {
+            Tolerance {
+                gaps: vec![1, 2, 3],
+                device_offset: 3,
+            }
+        }
+    }
 
     #[derive(Debug)]
     pub struct Adapters {
+        /// Sorted, starting with the outlet and ending with the device
         adapters: Vec<u32>,
+        tolerance: Tolerance,
     }
 
-    #[derive(Debug, PartialEq)]
+    /// How often each gap shows up in the chain using every adapter
+    #[derive(Debug, PartialEq, Default)]
     pub struct JoltageDiff {
-        pub one: u32,
-        pub three: u32,
+        pub counts: BTreeMap<u32, u32>,
     }
 
     impl JoltageDiff {
+        pub fn count(&self, gap: u32) -> u32 {
+            self.counts.get(&gap).copied().unwrap_or_default()
+        }
+
         pub fn product(&self) -> u32 {
-            self.one * self.three
+            self.count(1) * self.count(3)
         }
     }
 
@@ -38,47 +153,87 @@ mod adapters {
         I: IntoIterator<Item = u32>,
     {
         fn from(value: I) -> Self {
-            let mut adapters: Vec<_> = iter::once(0).chain(value.into_iter().sorted()).collect();
-            adapters.push(adapters.last().unwrap() + 3);
-            Adapters { adapters }
+            Adapters::new(value, Tolerance::default())
         }
     }
 
     impl Adapters {
-        pub fn from_file(name: &str) -> anyhow::Result<Self> {
-            Ok(Self::from(
-                BufReader::new(File::open(name).unwrap())
-                    .lines()
-                    .map(|line| -> anyhow::Result<u32> { Ok(line?.parse::<u32>()?) })
-                    .collect::<anyhow::Result<Vec<_>>>()?,
-            ))
+        /// Panics if the device would be past `u32::MAX` jolts, which
+        /// `try_new` reports instead
+        pub fn new<I>(value: I, tolerance: Tolerance) -> Self
+        where
+            I: IntoIterator<Item = u32>,
+        {
+            Self::try_new(value, tolerance).unwrap()
+        }
+
+        pub fn try_new<I>(value: I, tolerance: Tolerance) -> Result<Self, AdapterError>
+        where
+            I: IntoIterator<Item = u32>,
+        {
+            let mut adapters: Vec<_> = iter::once(0).chain(value.into_iter().sorted()).collect();
+            let highest = *adapters.last().unwrap();
+            let device = highest.checked_add(tolerance.device_offset).ok_or(
+                AdapterError::DeviceTooHigh {
+                    highest,
+                    offset: tolerance.device_offset,
+                },
+            )?;
+            adapters.push(device);
+            Ok(Adapters {
+                adapters,
+                tolerance,
+            })
+        }
+
+        pub fn from_file(name: &str, tolerance: Tolerance) -> Result<Self, AdapterError> {
+            let io_error = |e: io::Error| AdapterError::Io(format!("{name}: {e}"));
+            let mut values = Vec::new();
+            for (idx, line) in BufReader::new(File::open(name).map_err(io_error)?)
+                .lines()
+                .enumerate()
+            {
+                let line = line.map_err(io_error)?;
+                let value = line.trim().parse().map_err(|_| AdapterError::Parse {
+                    line: idx + 1,
+                    text: line.clone(),
+                })?;
+                values.push(value);
+            }
+            Self::try_new(values, tolerance)
+        }
+
+        pub fn tolerance(&self) -> &Tolerance {
+            &self.tolerance
         }
 
         pub fn differences(&self) -> JoltageDiff {
-            let mut diffs = JoltageDiff { one: 0, three: 0 };
-            for (first, second) in self.adapters.iter().zip(self.adapters.iter().skip(1)) {
-                match second - first {
-                    1 => diffs.one += 1,
-                    3 => diffs.three += 1,
-                    _ => (),
-                }
+            let mut diffs = JoltageDiff::default();
+            for (first, second) in self.adapters.iter().tuple_windows() {
+                *diffs.counts.entry(second - first).or_default() += 1;
             }
             diffs
         }
 
-        pub fn num_arrangements(&self) -> usize {
-            let mut counts: HashMap<u32, usize> =
-                self.adapters.iter().cloned().map(|x| (x, 0)).collect();
-            *counts.get_mut(&0).unwrap() = 1;
-            for adapter in &self.adapters {
-                let num = *counts.get(adapter).unwrap();
-                for idx in adapter + 1..adapter + 4 {
-                    if let Some(count) = counts.get_mut(&idx) {
-                        *count += num;
-                    }
-                }
-            }
-            *counts.get(self.adapters.last().unwrap()).unwrap()
+        /// Sorted, starting with the outlet and ending with the device
+        pub fn joltages(&self) -> &[u32] {
+            &self.adapters
+        }
+
+        /// Indexes of the adapters that can follow the one at `idx`
+        pub fn successors(&self, idx: usize) -> impl Iterator<Item = usize> + '_ {
+            let joltage = self.adapters[idx];
+            (idx + 1..self.adapters.len())
+                .take_while(move |next| self.adapters[*next] - joltage <= self.tolerance.max_gap())
+                .filter(move |next| self.tolerance.allows(self.adapters[*next] - joltage))
+        }
+
+        pub fn num_arrangements(&self) -> BigUint {
+            self.arrangements().count().clone()
+        }
+
+        pub fn arrangements(&self) -> Arrangements<'_> {
+            Arrangements::new(self)
         }
     }
 }
@@ -86,30 +241,69 @@ mod adapters {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use num_bigint::BigUint;
 
     #[test]
     fn part1() {
-        let diffs = Adapters::from_file("test-input").unwrap().differences();
-        assert_eq!(diffs, JoltageDiff { one: 22, three: 10 });
+        let diffs = Adapters::from_file("test-input", Tolerance::default())
+            .unwrap()
+            .differences();
+        assert_eq!(diffs.count(1), 22);
+        assert_eq!(diffs.count(3), 10);
+        assert_eq!(diffs.counts.len(), 2);
     }
 
     #[test]
     fn part2_small() {
         assert_eq!(
-            Adapters::from_file("test-input-small")
+            Adapters::from_file("test-input-small", Tolerance::default())
                 .unwrap()
                 .num_arrangements(),
-            8
+            8u32.into()
         );
     }
 
     #[test]
     fn part2() {
         assert_eq!(
-            Adapters::from_file("test-input")
+            Adapters::from_file("test-input", Tolerance::default())
                 .unwrap()
                 .num_arrangements(),
-            19208
+            19208u32.into()
+        );
+    }
+
+    #[test]
+    fn custom_tolerance() {
+        let tolerance = Tolerance::new(vec![2, 1], 1).unwrap();
+        let adapters = Adapters::new([1, 2, 3, 5], tolerance);
+        // 0 1 2 3 5 6
+        let diffs = adapters.differences();
+        assert_eq!(diffs.count(1), 4);
+        assert_eq!(diffs.count(2), 1);
+        // Paths through 1, 2, 3 from 0 to 3 with steps of 1 or 2, then 5, 6
+        assert_eq!(adapters.num_arrangements(), 3u32.into());
+
+        let strict = Tolerance::new(vec![3], 3).unwrap();
+        assert_eq!(
+            Adapters::new([1, 2], strict).num_arrangements(),
+            0u32.into()
         );
+        assert!(Tolerance::new(vec![0, 1], 3).is_err());
+        assert!(Tolerance::new(vec![], 3).is_err());
+    }
+
+    #[test]
+    fn long_chains() {
+        // Steps of 1, 2 or 3 along 0..=299 count like tribonacci numbers, and
+        // the device at 302 can only follow 299
+        let mut tribonacci = vec![BigUint::from(1u32), 1u32.into(), 2u32.into()];
+        for n in 3..300 {
+            let next = &tribonacci[n - 1] + &tribonacci[n - 2] + &tribonacci[n - 3];
+            tribonacci.push(next);
+        }
+        let count = Adapters::from(1..300).num_arrangements();
+        assert!(count.bits() > 128);
+        assert_eq!(count, tribonacci[299]);
     }
 }
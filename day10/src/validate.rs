@@ -0,0 +1,431 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, VecDeque},
+    error::Error,
+    fmt::{self, Display, Formatter},
+    iter,
+};
+
+use itertools::Itertools;
+
+use crate::adapters::Adapters;
+
+/// Two neighbouring joltages, counting the outlet and the device, too far
+/// apart for any chain to cross
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ImpossibleGap {
+    pub below: u32,
+    pub above: u32,
+    pub bridge: Bridging,
+}
+
+/// What it takes to close an impossible gap
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Bridging {
+    /// The fewest adapters that close it
+    Bridge(Bridge),
+    /// No steps the tolerance allows land exactly on `above`
+    Unbridgeable,
+    /// Too long to search, for the steps the tolerance allows
+    TooLarge,
+}
+
+/// Adapters closing a gap, kept as how often each step is taken so that
+/// even a gap of billions of jolts takes little room
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Bridge {
+    pub below: u32,
+    /// Each step with how many times it's taken, smallest step first
+    pub steps: Vec<(u32, u32)>,
+}
+
+impl Bridge {
+    /// How many adapters to add
+    pub fn count(&self) -> u64 {
+        self.steps
+            .iter()
+            .map(|(_, times)| u64::from(*times))
+            .sum::<u64>()
+            - 1
+    }
+
+    /// The new joltages in order, smallest steps first
+    pub fn joltages(&self) -> impl Iterator<Item = u32> + '_ {
+        let mut joltage = self.below;
+        self.steps
+            .iter()
+            .flat_map(|(step, times)| iter::repeat_n(*step, *times as usize))
+            .map(move |step| {
+                joltage += step;
+                joltage
+            })
+            .take(self.count() as usize)
+    }
+}
+
+impl Display for Bridge {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.count() <= 10 {
+            write!(f, "{}", self.joltages().join(", "))
+        } else {
+            let steps = self
+                .steps
+                .iter()
+                .map(|(step, times)| format!("{step} x{times}"));
+            write!(
+                f,
+                "{} adapters, stepping {}",
+                self.count(),
+                steps.format(", ")
+            )
+        }
+    }
+}
+
+impl Display for ImpossibleGap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Nothing connects {} to {} jolts", self.below, self.above)?;
+        match &self.bridge {
+            Bridging::Bridge(bridge) => write!(f, "; add {bridge}"),
+            Bridging::Unbridgeable => write!(f, "; no adapters can close it"),
+            Bridging::TooLarge => write!(f, "; too far apart to suggest adapters"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AdapterError {
+    /// The file couldn't be opened or read
+    Io(String),
+    /// A line that isn't a joltage
+    Parse { line: usize, text: String },
+    /// Gaps that leave no chain from the outlet to the device
+    Impossible(Vec<ImpossibleGap>),
+    /// No gap is too wide, but the allowed steps still can't reach the device
+    NoChain,
+    /// The device would sit above the highest joltage there is
+    DeviceTooHigh { highest: u32, offset: u32 },
+}
+
+impl Display for AdapterError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Can't read {e}"),
+            Self::Parse { line, text } => write!(f, "Line {line}: \"{text}\" isn't a joltage"),
+            Self::Impossible(gaps) => {
+                write!(f, "{} impossible gap(s):", gaps.len())?;
+                for gap in gaps {
+                    write!(f, "\n  {gap}")?;
+                }
+                Ok(())
+            }
+            Self::NoChain => write!(f, "No chain of allowed steps reaches the device"),
+            Self::DeviceTooHigh { highest, offset } => write!(
+                f,
+                "A device {offset} jolts above the {highest} jolt adapter would be past {}",
+                u32::MAX
+            ),
+        }
+    }
+}
+
+impl Error for AdapterError {}
+
+/// The most joltages, or remainders of the largest step, a bridge search
+/// keeps track of
+const SEARCH_LIMIT: u32 = 1 << 20;
+
+impl Adapters {
+    /// Checks there's at least one chain from the outlet to the device, and
+    /// if not, explains where it breaks
+    pub fn validate(&self) -> Result<(), AdapterError> {
+        let max_gap = self.tolerance().max_gap();
+        let gaps: Vec<_> = self
+            .joltages()
+            .iter()
+            .tuple_windows()
+            .filter(|(below, above)| *above - *below > max_gap)
+            .map(|(below, above)| ImpossibleGap {
+                below: *below,
+                above: *above,
+                bridge: self.bridge(*below, *above),
+            })
+            .collect();
+
+        if !gaps.is_empty() {
+            Err(AdapterError::Impossible(gaps))
+        } else if self.arrangements().count() == &0u32.into() {
+            Err(AdapterError::NoChain)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The fewest steps the tolerance allows from `below` to exactly `above`.
+    ///
+    /// Gaps up to `SEARCH_LIMIT` are searched breadth first. On longer ones,
+    /// swapping any step taken as often as the largest step's size for copies
+    /// of the largest leaves fewer steps, so the best bridge is some short
+    /// steps and then the largest step over and over. That only works while
+    /// the largest step is within `SEARCH_LIMIT` and the short steps fit in
+    /// the gap.
+    fn bridge(&self, below: u32, above: u32) -> Bridging {
+        let largest = self.tolerance().max_gap();
+        let len = above - below;
+        let short = if len <= SEARCH_LIMIT {
+            self.fewest_steps(len)
+        } else if largest <= SEARCH_LIMIT {
+            self.fewest_short_steps(len % largest)
+        } else {
+            return Bridging::TooLarge;
+        };
+        let Some(short) = short else {
+            return Bridging::Unbridgeable;
+        };
+        let total: u64 = short.iter().map(|step| u64::from(*step)).sum();
+        let Some(rest) = u64::from(len).checked_sub(total) else {
+            return Bridging::TooLarge;
+        };
+
+        let mut counts: BTreeMap<u32, u32> = BTreeMap::new();
+        for step in short {
+            *counts.entry(step).or_default() += 1;
+        }
+        *counts.entry(largest).or_default() += (rest / u64::from(largest)) as u32;
+        Bridging::Bridge(Bridge {
+            below,
+            steps: counts.into_iter().filter(|(_, times)| *times > 0).collect(),
+        })
+    }
+
+    /// Steps adding up to `len`, as few as possible, breadth first over
+    /// every total on the way
+    fn fewest_steps(&self, len: u32) -> Option<Vec<u32>> {
+        let len = len as usize;
+        let mut from: Vec<Option<usize>> = vec![None; len + 1];
+        let mut queue = VecDeque::from([0]);
+        while let Some(cur) = queue.pop_front() {
+            if cur == len {
+                break;
+            }
+            for gap in self.tolerance().gaps() {
+                let next = cur + *gap as usize;
+                if next <= len && from[next].is_none() {
+                    from[next] = Some(cur);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let mut steps = Vec::new();
+        let mut cur = len;
+        while cur != 0 {
+            let prev = from[cur]?;
+            steps.push((cur - prev) as u32);
+            cur = prev;
+        }
+        Some(steps)
+    }
+
+    /// Steps other than the largest whose total leaves `residue` after
+    /// dividing by the largest, costing each step by how far it falls short
+    /// of the largest. Searches the largest step's residues rather than every
+    /// joltage in the gap.
+    fn fewest_short_steps(&self, residue: u32) -> Option<Vec<u32>> {
+        let largest = self.tolerance().max_gap();
+        let mut cost: Vec<Option<u64>> = vec![None; largest as usize];
+        let mut from: Vec<Option<(u32, u32)>> = vec![None; largest as usize];
+        cost[0] = Some(0);
+        let mut queue = BinaryHeap::from([Reverse((0, 0))]);
+        while let Some(Reverse((so_far, cur))) = queue.pop() {
+            if cur == residue {
+                break;
+            }
+            if cost[cur as usize].is_some_and(|best| best < so_far) {
+                continue;
+            }
+            for step in self
+                .tolerance()
+                .gaps()
+                .iter()
+                .filter(|step| **step < largest)
+            {
+                let next = (cur + step) % largest;
+                let next_cost = so_far + u64::from(largest - step);
+                if cost[next as usize].is_none_or(|best| next_cost < best) {
+                    cost[next as usize] = Some(next_cost);
+                    from[next as usize] = Some((cur, *step));
+                    queue.push(Reverse((next_cost, next)));
+                }
+            }
+        }
+
+        cost[residue as usize]?;
+        let mut steps = Vec::new();
+        let mut cur = residue;
+        while cur != 0 {
+            let (prev, step) = from[cur as usize].unwrap();
+            steps.push(step);
+            cur = prev;
+        }
+        Some(steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::Tolerance;
+
+    #[test]
+    fn valid() {
+        let adapters = Adapters::from_file("test-input", Tolerance::default()).unwrap();
+        assert_eq!(adapters.validate(), Ok(()));
+    }
+
+    #[test]
+    fn impossible_gaps() {
+        let adapters = Adapters::new([1, 9, 10, 14], Tolerance::default());
+        assert_eq!(
+            adapters.validate(),
+            Err(AdapterError::Impossible(vec![
+                ImpossibleGap {
+                    below: 1,
+                    above: 9,
+                    bridge: Bridging::Bridge(Bridge {
+                        below: 1,
+                        steps: vec![(2, 1), (3, 2)]
+                    }),
+                },
+                ImpossibleGap {
+                    below: 10,
+                    above: 14,
+                    bridge: Bridging::Bridge(Bridge {
+                        below: 10,
+                        steps: vec![(1, 1), (3, 1)]
+                    }),
+                },
+            ]))
+        );
+        assert_eq!(
+            adapters.validate().unwrap_err().to_string(),
+            "2 impossible gap(s):
+  Nothing connects 1 to 9 jolts; add 3, 6
+  Nothing connects 10 to 14 jolts; add 11"
+        );
+
+        // The device itself can be out of reach
+        let far_device = Adapters::new([1], Tolerance::new(vec![1, 2], 5).unwrap());
+        assert_eq!(
+            far_device.validate(),
+            Err(AdapterError::Impossible(vec![ImpossibleGap {
+                below: 1,
+                above: 6,
+                bridge: Bridging::Bridge(Bridge {
+                    below: 1,
+                    steps: vec![(1, 1), (2, 2)]
+                }),
+            }]))
+        );
+    }
+
+    #[test]
+    fn huge_gaps() {
+        let adapters = Adapters::new([4_000_000_000], Tolerance::default());
+        let Err(AdapterError::Impossible(gaps)) = adapters.validate() else {
+            panic!("The gap from the outlet should be impossible");
+        };
+        let Bridging::Bridge(bridge) = &gaps[0].bridge else {
+            panic!("Steps of 1 can close any gap");
+        };
+        // 4 billion is one more than a multiple of 3
+        assert_eq!(bridge.steps, [(1, 1), (3, 1_333_333_333)]);
+        assert_eq!(bridge.count(), 1_333_333_333);
+        assert_eq!(bridge.joltages().take(3).collect::<Vec<_>>(), [1, 4, 7]);
+        assert_eq!(
+            gaps[0].to_string(),
+            "Nothing connects 0 to 4000000000 jolts; add 1333333333 adapters, stepping 1 x1, 3 x1333333333"
+        );
+
+        // Fours and sevens make every total from 18 up but not 17, and 35 is
+        // quickest as five sevens
+        let tolerance = Tolerance::new(vec![4, 7], 0).unwrap();
+        let bridge = |tolerance: &Tolerance, above| match Adapters::new([above], tolerance.clone())
+            .bridge(0, above)
+        {
+            Bridging::Bridge(bridge) => Some(bridge),
+            Bridging::Unbridgeable => None,
+            Bridging::TooLarge => panic!("{above} should be searchable"),
+        };
+        assert_eq!(bridge(&tolerance, 17), None);
+        assert_eq!(bridge(&tolerance, 35).unwrap().steps, [(7, 5)]);
+        let long = bridge(&tolerance, 1_000_000).unwrap();
+        assert_eq!(long.steps, [(4, 2), (7, 142_856)]);
+        assert_eq!(long.joltages().last(), Some(1_000_000 - 7));
+
+        // A large step makes the remainder search wide, but it stays within
+        // the limit: 4 billion is 61035 steps of 65536 and 10240 more
+        let wide = Tolerance::new(vec![1, 65_536], 3).unwrap();
+        let long = bridge(&wide, 4_000_000_000).unwrap();
+        assert_eq!(long.steps, [(1, 10_240), (65_536, 61_035)]);
+
+        // Past the limit for both the gap and the largest step, there's no
+        // suggestion rather than a search too big to run
+        let wider = Tolerance::new(vec![1, 3_000_000], 3).unwrap();
+        let adapters = Adapters::new([4_000_000_000], wider);
+        let Err(AdapterError::Impossible(gaps)) = adapters.validate() else {
+            panic!("The gap from the outlet should be impossible");
+        };
+        assert_eq!(gaps[0].bridge, Bridging::TooLarge);
+        assert_eq!(
+            gaps[0].to_string(),
+            "Nothing connects 0 to 4000000000 jolts; too far apart to suggest adapters"
+        );
+        // Making up the remainder 5 from steps one short of the largest takes
+        // far more than the gap itself
+        let near = Tolerance::new(vec![(1 << 20) - 1, 1 << 20], 0).unwrap();
+        let above = (1 << 20) + 5;
+        assert_eq!(
+            Adapters::new([above], near).bridge(0, above),
+            Bridging::TooLarge
+        );
+
+        assert_eq!(
+            Adapters::try_new([u32::MAX - 1], Tolerance::default()).unwrap_err(),
+            AdapterError::DeviceTooHigh {
+                highest: u32::MAX - 1,
+                offset: 3
+            }
+        );
+    }
+
+    #[test]
+    fn unbridgeable() {
+        let threes = Tolerance::new(vec![3], 3).unwrap();
+        let adapters = Adapters::new([3, 7], threes.clone());
+        assert_eq!(
+            adapters.validate(),
+            Err(AdapterError::Impossible(vec![ImpossibleGap {
+                below: 3,
+                above: 7,
+                bridge: Bridging::Unbridgeable,
+            }]))
+        );
+        assert_eq!(
+            Adapters::new([1, 2], threes).validate(),
+            Err(AdapterError::NoChain)
+        );
+    }
+
+    #[test]
+    fn bad_files() {
+        assert!(matches!(
+            Adapters::from_file("no-such-file", Tolerance::default()),
+            Err(AdapterError::Io(_))
+        ));
+        assert!(matches!(
+            Adapters::from_file("src/main.rs", Tolerance::default()),
+            Err(AdapterError::Parse { line: 1, .. })
+        ));
+    }
+}
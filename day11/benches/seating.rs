@@ -0,0 +1,60 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use day11_lib::{
+    automaton::ChangedRows, neighbours::NeighbourGraph, Seating, ADJACENT_RULES, SEEN_RULES,
+};
+
+/// A square layout with about one floor tile in seven, always the same
+fn layout(size: usize) -> Seating {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let rows: Vec<String> = (0..size)
+        .map(|_| {
+            (0..size)
+                .map(|_| {
+                    state = state
+                        .wrapping_mul(6_364_136_223_846_793_005)
+                        .wrapping_add(1_442_695_040_888_963_407);
+                    if (state >> 33).is_multiple_of(7) {
+                        '.'
+                    } else {
+                        'L'
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    Seating::try_from(rows).unwrap()
+}
+
+/// Random layouts don't always settle under the part 1 rules, so runs are a
+/// fixed number of generations
+const GENERATIONS: usize = 10;
+
+fn seating(c: &mut Criterion) {
+    for size in [100, 400, 1000] {
+        let mut group = c.benchmark_group(format!("seating_{size}"));
+        group.sample_size(10);
+        let start = layout(size);
+
+        group.bench_function("line_of_sight_graph", |b| {
+            b.iter(|| NeighbourGraph::new(&start.seats, &SEEN_RULES))
+        });
+        for (name, rules) in [("adjacent", ADJACENT_RULES), ("seen", SEEN_RULES)] {
+            let graph = NeighbourGraph::new(&start.seats, &rules);
+            group.bench_function(format!("{name}_generations"), |b| {
+                b.iter_batched_ref(
+                    || (start.seats.clone(), ChangedRows::all(size)),
+                    |(seats, changed)| {
+                        for _ in 0..GENERATIONS {
+                            seats.step(&rules, &graph, changed);
+                        }
+                    },
+                    BatchSize::LargeInput,
+                )
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, seating);
+criterion_main!(benches);
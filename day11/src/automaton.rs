@@ -0,0 +1,608 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fmt::{self, Display, Formatter},
+    hash::{Hash, Hasher},
+    mem,
+    ops::Index,
+    str::FromStr,
+};
+
+use anyhow::bail;
+use rayon::prelude::*;
+
+use crate::neighbours::NeighbourGraph;
+
+/// The eight directions out of a cell, as (row, column) steps
+const DIRECTIONS: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+/// The six directions out of a hexagon in axial coordinates, with rows
+/// along `r` and columns along `q`
+const HEX_DIRECTIONS: [(isize, isize); 6] = [(-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0)];
+
+/// How cells are laid out and what happens at the edges
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Topology {
+    /// Squares, with nothing past the edges
+    #[default]
+    Bounded,
+    /// Squares, with each edge joined to the opposite one
+    Toroidal,
+    /// Hexagons in axial coordinates, with nothing past the edges. Row `r`
+    /// is drawn `r` half cells to the right of row 0, so `(q, r - 1)` is
+    /// above left and `(q + 1, r - 1)` above right.
+    Hexagonal,
+}
+
+impl Topology {
+    fn directions(self) -> &'static [(isize, isize)] {
+        match self {
+            Self::Hexagonal => &HEX_DIRECTIONS,
+            _ => &DIRECTIONS,
+        }
+    }
+}
+
+impl FromStr for Topology {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bounded" => Ok(Self::Bounded),
+            "torus" => Ok(Self::Toroidal),
+            "hex" => Ok(Self::Hexagonal),
+            _ => bail!("Unknown topology {s}, expected bounded, torus or hex"),
+        }
+    }
+}
+
+/// Which cells count as neighbours of a cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighbourhood {
+    /// The eight surrounding cells, or six on hexagons
+    Adjacent,
+    /// The first cell in each direction that isn't transparent
+    LineOfSight,
+    /// Every other cell at most this many steps away
+    Radius(usize),
+}
+
+/// How a grid of `S` changes from one generation to the next
+#[derive(Debug, Clone, Copy)]
+pub struct Rules<S> {
+    pub neighbourhood: Neighbourhood,
+    /// States that line of sight passes over
+    pub transparent: fn(S) -> bool,
+    /// The next state of a cell, from its state and its neighbours' states
+    pub transition: fn(S, &[S]) -> S,
+}
+
+/// How a run of generations ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Nothing changes after this generation
+    Stable { generation: usize },
+    /// Generation `start` comes back every `period` generations, with
+    /// `period` above 1
+    Cycle { start: usize, period: usize },
+    /// Still going after this many generations
+    LimitReached { generations: usize },
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stable { generation } => write!(f, "Stable from generation {generation}"),
+            Self::Cycle { start, period } => {
+                write!(
+                    f,
+                    "Repeats every {period} generations from generation {start}"
+                )
+            }
+            Self::LimitReached { generations } => {
+                write!(f, "Still changing after {generations} generations")
+            }
+        }
+    }
+}
+
+/// Rows that changed in the last step, so the next step can skip rows that
+/// only look at unchanged ones. Only means anything for a run of steps on
+/// one grid with the same rules and neighbour graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedRows(Vec<bool>);
+
+impl ChangedRows {
+    /// For the first step of a run, when nothing can be skipped
+    pub fn all(height: usize) -> Self {
+        Self(vec![true; height])
+    }
+}
+
+/// A rectangular grid of cells, all updated together each generation.
+///
+/// Cells are stored row after row, with a second buffer for the next
+/// generation to be written into.
+#[derive(Debug, Clone)]
+pub struct Grid<S> {
+    cells: Vec<S>,
+    /// The generation before `cells`, to be overwritten by the one after
+    next: Vec<S>,
+    width: usize,
+    height: usize,
+    topology: Topology,
+}
+
+impl<S: Clone> TryFrom<Vec<Vec<S>>> for Grid<S> {
+    type Error = anyhow::Error;
+
+    fn try_from(rows: Vec<Vec<S>>) -> Result<Self, Self::Error> {
+        let width = rows.first().map_or(0, Vec::len);
+        if let Some(row) = rows.iter().position(|row| row.len() != width) {
+            bail!("Row {} is {} wide, not {width}", row + 1, rows[row].len());
+        }
+        let height = rows.len();
+        // Neighbour graphs number cells in 32 bits
+        if (width as u64) * (height as u64) > u64::from(u32::MAX) {
+            bail!("{width} by {height} is more than {} cells", u32::MAX);
+        }
+        let cells: Vec<S> = rows.into_iter().flatten().collect();
+        Ok(Self {
+            next: cells.clone(),
+            cells,
+            width,
+            height,
+            topology: Topology::Bounded,
+        })
+    }
+}
+
+impl<S: PartialEq> PartialEq for Grid<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width && self.topology == other.topology && self.cells == other.cells
+    }
+}
+
+impl<S: Eq> Eq for Grid<S> {}
+
+impl<S> Index<usize> for Grid<S> {
+    type Output = [S];
+
+    fn index(&self, row: usize) -> &Self::Output {
+        &self.cells[row * self.width..(row + 1) * self.width]
+    }
+}
+
+impl<S: Copy + Eq> Grid<S> {
+    /// The same cells, laid out as `topology`
+    pub fn with_topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> Option<S> {
+        (row < self.height && col < self.width).then(|| self.cells[row * self.width + col])
+    }
+
+    /// The cell at `row * width + col`
+    #[inline]
+    pub fn at(&self, idx: usize) -> S {
+        self.cells[idx]
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = S> + '_ {
+        self.cells.iter().copied()
+    }
+
+    pub fn count(&self, state: S) -> usize {
+        self.cells().filter(|cell| *cell == state).count()
+    }
+
+    /// The position `steps` times `(d_row, d_col)` away, if it's on the grid
+    fn offset(
+        &self,
+        row: usize,
+        col: usize,
+        (d_row, d_col): (isize, isize),
+        steps: isize,
+    ) -> Option<(usize, usize)> {
+        if self.topology == Topology::Toroidal {
+            let wrap = |pos: usize, step: isize, size: usize| {
+                (pos as isize + step).rem_euclid(size as isize) as usize
+            };
+            return Some((
+                wrap(row, d_row * steps, self.height),
+                wrap(col, d_col * steps, self.width),
+            ));
+        }
+        let row = row.checked_add_signed(d_row * steps)?;
+        let col = col.checked_add_signed(d_col * steps)?;
+        (row < self.height() && col < self.width).then_some((row, col))
+    }
+
+    /// Calls `found` with the position of each neighbour of a cell. On a
+    /// small torus, a cell reached by more than one direction or sightline
+    /// is only found once, and a cell never neighbours itself.
+    pub fn neighbour_positions<F>(&self, row: usize, col: usize, rules: &Rules<S>, mut found: F)
+    where
+        F: FnMut(usize, usize),
+    {
+        let directions = self.topology.directions();
+        let mut positions: Vec<_> = match rules.neighbourhood {
+            Neighbourhood::Adjacent => directions
+                .iter()
+                .filter_map(|direction| self.offset(row, col, *direction, 1))
+                .collect(),
+            // On a torus every line comes back round, so looking stops there
+            Neighbourhood::LineOfSight => directions
+                .iter()
+                .filter_map(|direction| {
+                    (1..)
+                        .map(|steps| self.offset(row, col, *direction, steps))
+                        .take_while(|pos| *pos != Some((row, col)))
+                        .find(|pos| {
+                            !pos.is_some_and(|(row, col)| {
+                                (rules.transparent)(self.at(row * self.width + col))
+                            })
+                        })
+                        .flatten()
+                })
+                .collect(),
+            Neighbourhood::Radius(radius) => {
+                let radius = radius as isize;
+                (-radius..=radius)
+                    .flat_map(|d_row| (-radius..=radius).map(move |d_col| (d_row, d_col)))
+                    .filter(|(d_row, d_col)| {
+                        self.topology != Topology::Hexagonal || (d_row + d_col).abs() <= radius
+                    })
+                    .filter_map(|direction| self.offset(row, col, direction, 1))
+                    .collect()
+            }
+        };
+        if self.topology == Topology::Toroidal {
+            positions.sort_unstable();
+            positions.dedup();
+        }
+        positions
+            .into_iter()
+            .filter(|pos| *pos != (row, col))
+            .for_each(|(row, col)| found(row, col));
+    }
+}
+
+impl<S: Copy + Eq + Send + Sync> Grid<S> {
+    /// Moves every cell on one generation using neighbours worked out
+    /// beforehand, and says whether any changed.
+    ///
+    /// Rows are worked out in parallel, skipping any that only look at rows
+    /// which `changed` says didn't change last time. Pass the same `changed`
+    /// to every step of a run, starting from `ChangedRows::all`.
+    pub fn step(
+        &mut self,
+        rules: &Rules<S>,
+        graph: &NeighbourGraph,
+        changed: &mut ChangedRows,
+    ) -> bool {
+        let width = self.width;
+        if width == 0 {
+            return false;
+        }
+        // How many rows changed above each row, so a row can tell in one
+        // subtraction whether anything it looks at moved
+        let mut changed_above = Vec::with_capacity(self.height + 1);
+        changed_above.push(0);
+        for row in &changed.0 {
+            changed_above.push(changed_above.last().unwrap() + usize::from(*row));
+        }
+
+        let cells = &self.cells;
+        changed.0 = self
+            .next
+            .par_chunks_mut(width)
+            .enumerate()
+            .map(|(row, next)| {
+                // A skipped row didn't change last step either, so the
+                // generation before is already the same as this one
+                let sources = graph.source_rows(row);
+                if changed_above[sources.end() + 1] == changed_above[*sources.start()] {
+                    return false;
+                }
+                let mut neighbours = Vec::new();
+                let mut changed = false;
+                let current = &cells[row * width..(row + 1) * width];
+                for (col, (cell, next)) in current.iter().zip(next).enumerate() {
+                    neighbours.clear();
+                    neighbours.extend(
+                        graph
+                            .neighbours(row * width + col)
+                            .iter()
+                            .map(|n| cells[*n as usize]),
+                    );
+                    *next = (rules.transition)(*cell, &neighbours);
+                    changed |= next != cell;
+                }
+                changed
+            })
+            .collect();
+
+        mem::swap(&mut self.cells, &mut self.next);
+        changed.0.contains(&true)
+    }
+
+    /// Steps until a generation changes nothing, a generation repeats, or
+    /// `max_generations` have run. Neighbours are only worked out once, so
+    /// cells must never change into or out of a transparent state.
+    ///
+    /// Generations are remembered by a 64-bit hash rather than kept whole.
+    pub fn run(&mut self, rules: &Rules<S>, max_generations: Option<usize>) -> Outcome
+    where
+        S: Hash,
+    {
+        self.run_with(rules, max_generations, |_| ())
+    }
+
+    /// Same as `run`, calling `on_generation` with each new generation
+    pub fn run_with<F>(
+        &mut self,
+        rules: &Rules<S>,
+        max_generations: Option<usize>,
+        mut on_generation: F,
+    ) -> Outcome
+    where
+        S: Hash,
+        F: FnMut(&Self),
+    {
+        let graph = NeighbourGraph::new(self, rules);
+        let mut changed = ChangedRows::all(self.height);
+        let mut seen = HashMap::from([(self.state_hash(), 0)]);
+        let mut generation = 0;
+        loop {
+            if max_generations.is_some_and(|max| generation == max) {
+                return Outcome::LimitReached {
+                    generations: generation,
+                };
+            }
+            if !self.step(rules, &graph, &mut changed) {
+                return Outcome::Stable { generation };
+            }
+            generation += 1;
+            on_generation(self);
+            if let Some(start) = seen.insert(self.state_hash(), generation) {
+                return Outcome::Cycle {
+                    start,
+                    period: generation - start,
+                };
+            }
+        }
+    }
+
+    fn state_hash(&self) -> u64
+    where
+        S: Hash,
+    {
+        let mut hasher = DefaultHasher::new();
+        self.cells.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Hexagons come out with each row one space further right than the last,
+/// and spaces between cells
+impl<S: Display> Display for Grid<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let hex = self.topology == Topology::Hexagonal;
+        for (idx, row) in self.cells.chunks(self.width.max(1)).enumerate() {
+            for (col, cell) in row.iter().enumerate() {
+                if hex {
+                    let gap = if col == 0 { idx } else { 1 };
+                    write!(f, "{:gap$}", "")?;
+                }
+                write!(f, "{}", cell)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Conway's life, with `true` for live cells
+    fn life(neighbourhood: Neighbourhood) -> Rules<bool> {
+        Rules {
+            neighbourhood,
+            transparent: |_| false,
+            transition: |alive, neighbours| {
+                let live = neighbours.iter().filter(|cell| **cell).count();
+                live == 3 || (alive && live == 2)
+            },
+        }
+    }
+
+    fn grid(rows: &[&str]) -> Grid<bool> {
+        rows.iter()
+            .map(|row| row.chars().map(|c| c == '#').collect())
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap()
+    }
+
+    /// States of the neighbours of a cell
+    fn neighbours(cells: &Grid<bool>, row: usize, col: usize, rules: &Rules<bool>) -> Vec<bool> {
+        let mut out = Vec::new();
+        cells.neighbour_positions(row, col, rules, |row, col| out.push(cells[row][col]));
+        out
+    }
+
+    #[test]
+    fn blinker() {
+        let mut cells = grid(&[".....", "..#..", "..#..", "..#..", "....."]);
+        let start = cells.clone();
+        let rules = life(Neighbourhood::Adjacent);
+        let graph = NeighbourGraph::new(&cells, &rules);
+        let mut changed = ChangedRows::all(cells.height());
+        assert!(cells.step(&rules, &graph, &mut changed));
+        assert_eq!(cells, grid(&[".....", ".....", ".###.", ".....", "....."]));
+        assert!(cells.step(&rules, &graph, &mut changed));
+        assert_eq!(cells, start);
+    }
+
+    /// Every cell worked out from scratch, with no rows skipped
+    fn full_step(cells: &Grid<bool>, rules: &Rules<bool>) -> Grid<bool> {
+        (0..cells.height())
+            .map(|row| {
+                (0..cells.width())
+                    .map(|col| {
+                        (rules.transition)(cells[row][col], &neighbours(cells, row, col, rules))
+                    })
+                    .collect()
+            })
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap()
+    }
+
+    #[test]
+    fn skipped_rows() {
+        // A glider crossing rows that have been still for a while, next to a
+        // blinker that keeps its own rows busy
+        let mut rows = vec!["................"; 16];
+        rows[1] = ".#..............";
+        rows[2] = "..#.......#.....";
+        rows[3] = "###.......#.....";
+        rows[4] = "..........#.....";
+        let mut cells = grid(&rows);
+        let mut expected = cells.clone();
+        let rules = life(Neighbourhood::Adjacent);
+        let graph = NeighbourGraph::new(&cells, &rules);
+        let mut changed = ChangedRows::all(cells.height());
+        for _ in 0..40 {
+            let next = full_step(&expected, &rules);
+            assert_eq!(cells.step(&rules, &graph, &mut changed), next != expected);
+            expected = next;
+            assert_eq!(cells, expected);
+        }
+        assert_eq!(cells.count(true), 5 + 3);
+    }
+
+    #[test]
+    fn outcomes() {
+        let rules = life(Neighbourhood::Adjacent);
+        // The lone cell dies off, then the blinker flips forever
+        let mut blinker = grid(&["#.....", "......", "..###.", "......", "......"]);
+        assert_eq!(
+            blinker.clone().run(&rules, None),
+            Outcome::Cycle {
+                start: 1,
+                period: 2
+            }
+        );
+        assert_eq!(
+            blinker.run(&rules, Some(1)),
+            Outcome::LimitReached { generations: 1 }
+        );
+
+        let mut block = grid(&["....", ".##.", ".#..", "...."]);
+        assert_eq!(block.run(&rules, None), Outcome::Stable { generation: 1 });
+        assert_eq!(block.count(true), 4);
+    }
+
+    #[test]
+    fn neighbourhoods() {
+        let cells = grid(&["#...#", ".....", "#.#..", ".....", "#...#"]);
+        let live = |neighbourhood| {
+            let out = neighbours(&cells, 2, 2, &life(neighbourhood));
+            (out.len(), out.iter().filter(|cell| **cell).count())
+        };
+        assert_eq!(live(Neighbourhood::Adjacent), (8, 0));
+        assert_eq!(live(Neighbourhood::Radius(1)), (8, 0));
+        assert_eq!(live(Neighbourhood::Radius(2)), (24, 5));
+
+        let sight = Rules {
+            transparent: |cell: bool| !cell,
+            ..life(Neighbourhood::LineOfSight)
+        };
+        assert_eq!(neighbours(&cells, 2, 2, &sight), [true; 5]);
+        assert_eq!(neighbours(&cells, 0, 0, &sight), [true, true, true]);
+        assert!(Grid::try_from(vec![vec![true], vec![]]).is_err());
+        // Rows of nothing take no room, however long
+        assert!(Grid::try_from(vec![vec![(); 1 << 16]; 1 << 16]).is_err());
+    }
+
+    #[test]
+    fn topologies() {
+        let bounded = grid(&["#...#", ".....", "#.#..", ".....", "#...#"]);
+        let sight = |neighbourhood| Rules {
+            transparent: |cell: bool| !cell,
+            ..life(neighbourhood)
+        };
+
+        let torus = bounded.clone().with_topology(Topology::Toroidal);
+        let live = |row, col, rules| {
+            let out = neighbours(&torus, row, col, &rules);
+            (out.len(), out.iter().filter(|cell| **cell).count())
+        };
+        // The other three corners wrap round to touch this one
+        assert_eq!(live(0, 0, life(Neighbourhood::Adjacent)), (8, 3));
+        // Looking left and right both wrap round to the same cell, which
+        // counts once, and looking up or down comes back round with nothing seen
+        assert_eq!(live(2, 2, sight(Neighbourhood::LineOfSight)), (5, 5));
+        assert_eq!(live(2, 2, life(Neighbourhood::Radius(2))), (24, 5));
+        assert_eq!(live(2, 2, life(Neighbourhood::Radius(3))), (24, 5));
+
+        // On a 2x2 torus every direction lands on one of the other three cells
+        let small = grid(&["#.", ".#"]).with_topology(Topology::Toroidal);
+        let live = |row, col, rules| {
+            let out = neighbours(&small, row, col, &rules);
+            (out.len(), out.iter().filter(|cell| **cell).count())
+        };
+        assert_eq!(live(1, 1, life(Neighbourhood::Adjacent)), (3, 1));
+        assert_eq!(live(1, 1, life(Neighbourhood::Radius(1))), (3, 1));
+        // All four diagonals see the same seat
+        assert_eq!(live(1, 1, sight(Neighbourhood::LineOfSight)), (1, 1));
+        // A single row wraps left and right back onto itself
+        let row = grid(&["#.#"]).with_topology(Topology::Toroidal);
+        assert_eq!(
+            neighbours(&row, 0, 1, &life(Neighbourhood::Adjacent)),
+            [true, true]
+        );
+
+        let hex = bounded.with_topology(Topology::Hexagonal);
+        let live = |row, col, rules| {
+            let out = neighbours(&hex, row, col, &rules);
+            (out.len(), out.iter().filter(|cell| **cell).count())
+        };
+        assert_eq!(live(2, 2, life(Neighbourhood::Adjacent)), (6, 0));
+        // The sharp corners of the rhombus touch two cells, the blunt ones three
+        assert_eq!(live(0, 0, life(Neighbourhood::Adjacent)), (2, 0));
+        assert_eq!(live(0, 4, life(Neighbourhood::Adjacent)), (3, 0));
+        // Left to (2, 0), above right to (0, 4) and below left to (4, 0)
+        assert_eq!(live(2, 2, sight(Neighbourhood::LineOfSight)), (3, 3));
+        assert_eq!(live(2, 2, life(Neighbourhood::Radius(1))), (6, 0));
+        assert_eq!(live(2, 2, life(Neighbourhood::Radius(2))), (18, 3));
+        assert_eq!("torus".parse::<Topology>().unwrap(), Topology::Toroidal);
+        assert!("sphere".parse::<Topology>().is_err());
+    }
+}
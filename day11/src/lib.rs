@@ -0,0 +1,323 @@
+use std::{
+    convert::TryFrom,
+    fmt::Display,
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+use anyhow::anyhow;
+use automaton::{Grid, Neighbourhood, Outcome, Rules, Topology};
+use recorder::Recorder;
+
+pub mod automaton;
+pub mod neighbours;
+pub mod recorder;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Seat {
+    Floor,
+    Empty,
+    Occupied,
+}
+
+#[derive(Debug)]
+pub struct Seating {
+    pub seats: Grid<Seat>,
+}
+
+impl TryFrom<char> for Seat {
+    type Error = anyhow::Error;
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value {
+            '.' => Ok(Self::Floor),
+            'L' => Ok(Self::Empty),
+            '#' => Ok(Self::Occupied),
+            _ => Err(anyhow!("{} is not a seat code", value)),
+        }
+    }
+}
+
+impl Display for Seat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Floor => write!(f, "."),
+            Self::Empty => write!(f, "L"),
+            Self::Occupied => write!(f, "#"),
+        }
+    }
+}
+
+impl Seat {
+    pub fn is_floor(self) -> bool {
+        self == Self::Floor
+    }
+
+    /// Empty seats fill up with no one around, and occupied ones empty once
+    /// `limit` neighbours are occupied
+    pub fn next(self, neighbours: &[Seat], limit: usize) -> Self {
+        let occupied = || {
+            neighbours
+                .iter()
+                .filter(|seat| **seat == Self::Occupied)
+                .count()
+        };
+        match self {
+            Self::Empty if occupied() == 0 => Self::Occupied,
+            Self::Occupied if occupied() >= limit => Self::Empty,
+            seat => seat,
+        }
+    }
+}
+
+/// Part 1: people look at the eight seats around them, and leave at four
+pub const ADJACENT_RULES: Rules<Seat> = Rules {
+    neighbourhood: Neighbourhood::Adjacent,
+    transparent: Seat::is_floor,
+    transition: |seat, neighbours| seat.next(neighbours, 4),
+};
+
+/// Part 2: people look past the floor, and leave at five
+pub const SEEN_RULES: Rules<Seat> = Rules {
+    neighbourhood: Neighbourhood::LineOfSight,
+    transparent: Seat::is_floor,
+    transition: |seat, neighbours| seat.next(neighbours, 5),
+};
+
+impl Seating {
+    pub fn try_from<I, T>(value: I) -> Result<Self, anyhow::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        Self::parse(value, Topology::Bounded)
+    }
+
+    /// Hexagonal layouts are rows of axial coordinates, written the way
+    /// `Display` writes them: each row shifted half a cell right of the one
+    /// above, with spaces between cells, all of which are ignored
+    pub fn parse<I, T>(value: I, topology: Topology) -> Result<Self, anyhow::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        let hex = topology == Topology::Hexagonal;
+        let seats: Result<Vec<_>, _> = value
+            .into_iter()
+            .map(|row| {
+                row.as_ref()
+                    .chars()
+                    .filter(|c| !(hex && c.is_whitespace()))
+                    .map(Seat::try_from)
+                    .collect()
+            })
+            .collect();
+        let seats: Grid<Seat> = seats?.try_into()?;
+        Ok(Self {
+            seats: seats.with_topology(topology),
+        })
+    }
+
+    pub fn from_file(name: &str) -> Result<Self, anyhow::Error> {
+        Self::from_file_with(name, Topology::Bounded)
+    }
+
+    pub fn from_file_with(name: &str, topology: Topology) -> Result<Self, anyhow::Error> {
+        let file: Result<Vec<_>, _> = BufReader::new(File::open(name)?).lines().collect();
+        Self::parse(file?, topology)
+    }
+}
+
+impl Seating {
+    pub fn run(&mut self, rules: &Rules<Seat>, max_generations: Option<usize>) -> Outcome {
+        self.seats.run(rules, max_generations)
+    }
+
+    /// Same as `run`, capturing the starting layout and every generation
+    /// after it
+    pub fn record(
+        &mut self,
+        rules: &Rules<Seat>,
+        max_generations: Option<usize>,
+        recorder: &mut Recorder,
+    ) -> Outcome {
+        recorder.capture(self);
+        self.seats
+            .run_with(rules, max_generations, |seats| recorder.capture(seats))
+    }
+
+    pub fn occupied(&self) -> usize {
+        self.seats.count(Seat::Occupied)
+    }
+
+    /// Occupied seats once nothing changes, or an error if the seating
+    /// never settles
+    pub fn run_until_stable(&mut self, rules: &Rules<Seat>) -> anyhow::Result<usize> {
+        match self.run(rules, None) {
+            Outcome::Stable { .. } => Ok(self.occupied()),
+            outcome => Err(anyhow!("{outcome}")),
+        }
+    }
+
+    pub fn occupied_stable(&mut self) -> anyhow::Result<usize> {
+        self.run_until_stable(&ADJACENT_RULES)
+    }
+
+    pub fn seen_occupied_stable(&mut self) -> anyhow::Result<usize> {
+        self.run_until_stable(&SEEN_RULES)
+    }
+}
+
+impl Display for Seating {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.seats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Seat::*;
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let layout = Seating::from_file("test-input").unwrap();
+        assert_eq!(
+            layout.seats[0],
+            [Empty, Floor, Empty, Empty, Floor, Empty, Empty, Floor, Empty, Empty]
+        );
+    }
+
+    #[test]
+    fn part1() {
+        let mut layout = Seating::from_file("test-input").unwrap();
+        let count = layout.occupied_stable().unwrap();
+        println!("{layout}");
+        assert_eq!(count, 37);
+    }
+
+    #[test]
+    fn part2() {
+        let mut layout = Seating::from_file("test-input").unwrap();
+        let count = layout.seen_occupied_stable().unwrap();
+        println!("{layout}");
+        assert_eq!(count, 26);
+    }
+
+    #[test]
+    fn rules_in_turn() {
+        // Settled under one set of rules is no reason to skip rows under the
+        // next
+        let mut layout = Seating::from_file("test-input").unwrap();
+        assert_eq!(layout.run_until_stable(&ADJACENT_RULES).unwrap(), 37);
+        let mut fresh = Seating::try_from(layout.to_string().lines()).unwrap();
+        assert_eq!(
+            fresh.run(&SEEN_RULES, None),
+            Outcome::Stable { generation: 1 }
+        );
+        assert_eq!(
+            layout.run(&SEEN_RULES, None),
+            Outcome::Stable { generation: 1 }
+        );
+        assert_eq!(layout.occupied(), 34);
+        assert_eq!(layout.to_string(), fresh.to_string());
+    }
+
+    #[test]
+    fn hexagonal() {
+        let text = std::fs::read_to_string("test-input-hex").unwrap();
+        let mut layout = Seating::from_file_with("test-input-hex", Topology::Hexagonal).unwrap();
+        assert_eq!(layout.seats[1], [Empty; 4]);
+        assert_eq!(layout.to_string(), text);
+        // Everyone sits down, then the four seats with four or more people
+        // around them empty and nobody else moves
+        assert_eq!(
+            layout.run(&ADJACENT_RULES, None),
+            Outcome::Stable { generation: 2 }
+        );
+        assert_eq!(layout.occupied(), 6);
+        assert_eq!(layout.to_string(), "# # . #\n # L L L\n  . # L #\n");
+    }
+
+    #[test]
+    fn toroidal() {
+        let mut layout = Seating::from_file_with("test-input", Topology::Toroidal).unwrap();
+        let graph = neighbours::NeighbourGraph::new(&layout.seats, &ADJACENT_RULES);
+        assert_eq!(graph.neighbours(0).len(), 8);
+        assert_eq!(
+            layout.run(&ADJACENT_RULES, None),
+            Outcome::Stable { generation: 12 }
+        );
+        assert_eq!(layout.occupied(), 30);
+        assert_eq!(
+            layout.to_string(),
+            "L.#L.LL.LL
+#L#L#L#.#L
+L.L.L..L..
+#L##.##.##
+L.#L.LL.LL
+#.#L#L#.#L
+..L.L.....
+#L#LL#L###
+L.#LLLLL.L
+#.LL#L#.#L
+"
+        );
+
+        let mut layout = Seating::from_file_with("test-input", Topology::Toroidal).unwrap();
+        assert_eq!(
+            layout.run(&SEEN_RULES, None),
+            Outcome::Cycle {
+                start: 0,
+                period: 2
+            }
+        );
+    }
+
+    #[test]
+    fn toroidal_line_of_sight() {
+        let rows = ["..L.L.", ".....L", "..LLL.", "LLL...", "L.L..."];
+        // Without edges, sightlines wrap round to seats on the far side
+        let mut layout = Seating::parse(rows, Topology::Toroidal).unwrap();
+        assert_eq!(
+            layout.run(&SEEN_RULES, None),
+            Outcome::Stable { generation: 3 }
+        );
+        assert_eq!(layout.occupied(), 5);
+        assert_eq!(
+            layout.to_string(),
+            "..#.L.\n.....#\n..L#L.\n#LL...\nL.#...\n"
+        );
+
+        let mut bounded = Seating::try_from(rows).unwrap();
+        assert_eq!(
+            bounded.run(&SEEN_RULES, None),
+            Outcome::Stable { generation: 2 }
+        );
+        assert_eq!(bounded.occupied(), 8);
+    }
+
+    #[test]
+    fn radius() {
+        let rules = Rules {
+            neighbourhood: Neighbourhood::Radius(1),
+            ..ADJACENT_RULES
+        };
+        let mut layout = Seating::from_file("test-input").unwrap();
+        assert_eq!(layout.run_until_stable(&rules).unwrap(), 37);
+
+        // Counting seats two away, everyone sits down and gets up together forever
+        let rules = Rules {
+            neighbourhood: Neighbourhood::Radius(2),
+            ..ADJACENT_RULES
+        };
+        let mut layout = Seating::from_file("test-input").unwrap();
+        assert_eq!(
+            layout.run(&rules, None),
+            Outcome::Cycle {
+                start: 0,
+                period: 2
+            }
+        );
+    }
+}
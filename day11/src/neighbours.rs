@@ -0,0 +1,89 @@
+use std::ops::RangeInclusive;
+
+use crate::automaton::{Grid, Rules};
+
+/// Every cell's neighbours, worked out once and packed into two arrays: the
+/// neighbours of cell `idx` are `cells[starts[idx]..starts[idx + 1]]`, by
+/// `row * width + col`.
+///
+/// Cells fit in 32 bits, as grids are never bigger than that, but big
+/// neighbourhoods on big grids can have more edges.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NeighbourGraph {
+    starts: Vec<usize>,
+    cells: Vec<u32>,
+    /// The first and last rows each row's cells look at, including itself
+    rows: Vec<(u32, u32)>,
+}
+
+impl NeighbourGraph {
+    pub fn new<S: Copy + Eq>(grid: &Grid<S>, rules: &Rules<S>) -> Self {
+        let width = grid.width();
+        let mut starts = Vec::with_capacity(width * grid.height() + 1);
+        let mut cells = Vec::new();
+        let mut rows = Vec::with_capacity(grid.height());
+        starts.push(0);
+        for row in 0..grid.height() {
+            let (mut first, mut last) = (row, row);
+            for col in 0..width {
+                grid.neighbour_positions(row, col, rules, |row, col| {
+                    first = first.min(row);
+                    last = last.max(row);
+                    cells.push((row * width + col) as u32)
+                });
+                starts.push(cells.len());
+            }
+            rows.push((first as u32, last as u32));
+        }
+        Self {
+            starts,
+            cells,
+            rows,
+        }
+    }
+
+    #[inline]
+    pub fn neighbours(&self, idx: usize) -> &[u32] {
+        &self.cells[self.starts[idx]..self.starts[idx + 1]]
+    }
+
+    /// Rows whose changes can change `row`
+    #[inline]
+    pub fn source_rows(&self, row: usize) -> RangeInclusive<usize> {
+        let (first, last) = self.rows[row];
+        first as usize..=last as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Seating, SEEN_RULES};
+
+    #[test]
+    fn line_of_sight() {
+        let layout = Seating::try_from([
+            ".......#.",
+            "...#.....",
+            ".#.......",
+            ".........",
+            "..#L....#",
+            "....#....",
+            ".........",
+            "#........",
+            "...#.....",
+        ])
+        .unwrap();
+        let graph = NeighbourGraph::new(&layout.seats, &SEEN_RULES);
+        let width = layout.seats.width() as u32;
+        let mut seen: Vec<_> = graph.neighbours(4 * 9 + 3).to_vec();
+        seen.sort_unstable();
+        assert_eq!(seen.len(), 8);
+        assert_eq!(seen[0], 7);
+        assert_eq!(*seen.last().unwrap(), 8 * width + 3);
+        // Floor has neighbours too, though the seat rules never use them
+        assert_eq!(graph.neighbours(0).len(), 2);
+        assert_eq!(graph.source_rows(4), 0..=8);
+        assert_eq!(graph.source_rows(0), 0..=7);
+    }
+}
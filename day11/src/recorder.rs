@@ -0,0 +1,346 @@
+use std::{
+    fmt::{Display, Write as _},
+    fs::File,
+    io::{BufWriter, Write},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail};
+
+use crate::Seat;
+
+impl Seat {
+    /// RGB for pictures and coloured terminals
+    pub fn colour(self) -> [u8; 3] {
+        match self {
+            Self::Floor => [0x30, 0x30, 0x30],
+            Self::Empty => [0x4c, 0xaf, 0x50],
+            Self::Occupied => [0xe5, 0x39, 0x35],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// An asciinema v2 terminal recording
+    Asciicast,
+    /// One binary PPM picture per generation
+    Ppm,
+    /// One PNG picture per generation
+    Png,
+    /// One SVG that plays every generation in a loop
+    Svg,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cast" => Ok(Self::Asciicast),
+            "ppm" => Ok(Self::Ppm),
+            "png" => Ok(Self::Png),
+            "svg" => Ok(Self::Svg),
+            _ => bail!("Unknown format {s}, expected cast, ppm, png or svg"),
+        }
+    }
+}
+
+/// Generations of a seating area, kept as their `Display` text
+#[derive(Debug)]
+pub struct Recorder {
+    frames: Vec<String>,
+    /// Seconds each generation stays on screen
+    pub frame_seconds: f64,
+    /// Pixels along each side of a seat in pictures
+    pub scale: usize,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self {
+            frames: Vec::new(),
+            frame_seconds: 0.25,
+            scale: 8,
+        }
+    }
+
+    pub fn capture(&mut self, frame: &impl Display) {
+        self.frames.push(frame.to_string());
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// The seats of frame `idx`, read back from its text. Hexagonal frames
+    /// come out as the rows they're stored in.
+    fn seats(&self, idx: usize) -> anyhow::Result<Vec<Vec<Seat>>> {
+        self.frames[idx]
+            .lines()
+            .map(|row| {
+                row.chars()
+                    .filter(|c| !c.is_whitespace())
+                    .map(Seat::try_from)
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn size(&self) -> anyhow::Result<(usize, usize)> {
+        let seats = self.seats(0)?;
+        Ok((seats.first().map_or(0, Vec::len), seats.len()))
+    }
+
+    /// Writes in `format` to `path`, or for picture formats, to
+    /// `path-0000.ext` and so on
+    pub fn save(&self, format: Format, path: &str) -> anyhow::Result<()> {
+        if self.is_empty() {
+            bail!("Nothing recorded");
+        }
+        let create = |name: &str| -> anyhow::Result<_> {
+            File::create(name)
+                .map(BufWriter::new)
+                .map_err(|e| anyhow!("{name}: {e}"))
+        };
+        match format {
+            Format::Asciicast => self.write_asciicast(create(path)?),
+            Format::Svg => self.write_svg(create(path)?),
+            Format::Ppm | Format::Png => {
+                let ext = if format == Format::Ppm { "ppm" } else { "png" };
+                for idx in 0..self.len() {
+                    let out = create(&format!("{path}-{idx:04}.{ext}"))?;
+                    match format {
+                        Format::Ppm => self.write_ppm(idx, out)?,
+                        _ => self.write_png(idx, out)?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub fn write_asciicast<W: Write>(&self, mut out: W) -> anyhow::Result<()> {
+        let (width, height) = self.size()?;
+        writeln!(
+            out,
+            "{{\"version\": 2, \"width\": {width}, \"height\": {height}}}"
+        )?;
+        for idx in 0..self.len() {
+            // Home the cursor, then draw every seat in its colour
+            let mut text = String::from("\x1b[H");
+            for row in self.seats(idx)? {
+                for seat in row {
+                    let [r, g, b] = seat.colour();
+                    write!(text, "\x1b[38;2;{r};{g};{b}m{seat}")?;
+                }
+                text.push_str("\x1b[0m\r\n");
+            }
+            let time = idx as f64 * self.frame_seconds;
+            writeln!(out, "[{time:.3}, \"o\", \"{}\"]", json_escape(&text))?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// RGB pixels for frame `idx`, row by row
+    fn pixels(&self, idx: usize) -> anyhow::Result<(usize, usize, Vec<u8>)> {
+        let seats = self.seats(idx)?;
+        let (width, height) = (
+            seats.first().map_or(0, Vec::len) * self.scale,
+            seats.len() * self.scale,
+        );
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for row in &seats {
+            for _ in 0..self.scale {
+                for seat in row {
+                    for _ in 0..self.scale {
+                        pixels.extend(seat.colour());
+                    }
+                }
+            }
+        }
+        Ok((width, height, pixels))
+    }
+
+    pub fn write_ppm<W: Write>(&self, idx: usize, mut out: W) -> anyhow::Result<()> {
+        let (width, height, pixels) = self.pixels(idx)?;
+        write!(out, "P6\n{width} {height}\n255\n")?;
+        out.write_all(&pixels)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    pub fn write_png<W: Write>(&self, idx: usize, out: W) -> anyhow::Result<()> {
+        let (width, height, pixels) = self.pixels(idx)?;
+        let mut encoder = png::Encoder::new(out, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&pixels)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Every frame as a group that's only visible for its slot in the loop
+    pub fn write_svg<W: Write>(&self, mut out: W) -> anyhow::Result<()> {
+        let (width, height) = self.size()?;
+        let scale = self.scale;
+        let total = self.len() as f64 * self.frame_seconds;
+        let hex = |seat: Seat| {
+            let [r, g, b] = seat.colour();
+            format!("#{r:02x}{g:02x}{b:02x}")
+        };
+        writeln!(
+            out,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">",
+            width * scale,
+            height * scale
+        )?;
+        writeln!(
+            out,
+            "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>",
+            hex(Seat::Floor)
+        )?;
+
+        let count = self.len();
+        for idx in 0..count {
+            let start = idx as f64 / count as f64;
+            let end = (idx + 1) as f64 / count as f64;
+            let (values, key_times) = if idx == 0 {
+                ("visible;hidden".to_string(), format!("0;{end:.4}"))
+            } else {
+                (
+                    "hidden;visible;hidden".to_string(),
+                    format!("0;{start:.4};{end:.4}"),
+                )
+            };
+            writeln!(out, "<g visibility=\"hidden\">")?;
+            if count > 1 {
+                writeln!(
+                    out,
+                    "<animate attributeName=\"visibility\" values=\"{values}\" keyTimes=\"{key_times}\" dur=\"{total}s\" calcMode=\"discrete\" repeatCount=\"indefinite\"/>"
+                )?;
+            } else {
+                writeln!(out, "<set attributeName=\"visibility\" to=\"visible\"/>")?;
+            }
+            for (row, seats) in self.seats(idx)?.iter().enumerate() {
+                for (col, seat) in seats.iter().enumerate() {
+                    if !seat.is_floor() {
+                        writeln!(
+                            out,
+                            "<rect x=\"{}\" y=\"{}\" width=\"{scale}\" height=\"{scale}\" fill=\"{}\"/>",
+                            col * scale,
+                            row * scale,
+                            hex(*seat)
+                        )?;
+                    }
+                }
+            }
+            writeln!(out, "</g>")?;
+        }
+        writeln!(out, "</svg>")?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// Escapes text for a JSON string
+fn json_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c if c.is_control() => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Seating, ADJACENT_RULES};
+
+    fn recording() -> Recorder {
+        let mut seating = Seating::from_file("test-input").unwrap();
+        let mut recorder = Recorder::new();
+        recorder.scale = 2;
+        seating.record(&ADJACENT_RULES, None, &mut recorder);
+        recorder
+    }
+
+    #[test]
+    fn captures_every_generation() {
+        let recorder = recording();
+        // The start, then five changing generations
+        assert_eq!(recorder.len(), 6);
+        assert!(recorder.frames[0].starts_with("L.LL.LL.LL\n"));
+        assert!(recorder.frames[5].starts_with("#.#L.L#.##\n"));
+    }
+
+    #[test]
+    fn asciicast() {
+        let mut out = Vec::new();
+        recording().write_asciicast(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines[0], "{\"version\": 2, \"width\": 10, \"height\": 10}");
+        assert_eq!(lines.len(), 7);
+        assert!(lines[2].starts_with("[0.250, \"o\", \"\\u001b[H\\u001b[38;2;229;57;53m#"));
+        assert!(lines[2].ends_with("\\u001b[0m\\r\\n\"]"));
+    }
+
+    #[test]
+    fn pictures() {
+        let recorder = recording();
+        let mut ppm = Vec::new();
+        recorder.write_ppm(0, &mut ppm).unwrap();
+        let header = b"P6\n20 20\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 20 * 20 * 3);
+        // Top left seat is empty, the one next to it floor
+        assert_eq!(ppm[header.len()..][..3], Seat::Empty.colour());
+        assert_eq!(ppm[header.len() + 2 * 3..][..3], Seat::Floor.colour());
+
+        let mut png = Vec::new();
+        recorder.write_png(0, &mut png).unwrap();
+        let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut decoded = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut decoded).unwrap();
+        assert_eq!(decoded, ppm[header.len()..]);
+    }
+
+    #[test]
+    fn svg() {
+        let mut out = Vec::new();
+        recording().write_svg(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text
+            .starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"20\" height=\"20\">"));
+        assert_eq!(text.matches("<g ").count(), 6);
+        assert_eq!(text.matches("repeatCount=\"indefinite\"").count(), 6);
+        assert!(text.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!("cast".parse::<Format>().unwrap(), Format::Asciicast);
+        assert!("gif".parse::<Format>().is_err());
+        assert!(Recorder::new().save(Format::Svg, "unused.svg").is_err());
+    }
+}
@@ -3,16 +3,17 @@ use std::{
     io::{BufRead, BufReader},
 };
 
-use anyhow::anyhow;
+use anyhow::{anyhow, bail};
 
-fn main() {
+fn main() -> anyhow::Result<()> {
     let mut ship = Ship::default();
-    ship.apply_iter(Action::from_file("input").unwrap());
+    ship.apply_iter(Action::from_file("input")?)?;
     println!("{}", ship.manhattan_distance());
 
     let mut waypoint = Waypoint::default();
-    waypoint.apply_iter(Action::from_file("input").unwrap());
+    waypoint.apply_iter(Action::from_file("input")?)?;
     println!("{}", waypoint.manhattan_distance());
+    Ok(())
 }
 
 #[derive(Debug)]
@@ -21,30 +22,28 @@ enum Action {
     South(u16),
     East(u16),
     West(u16),
-    Left(u8),  // 90 degree (1/2 pi radians) increments
-    Right(u8), // 90 degree (1/2 pi radians) increments
+    Left(u16),  // Degrees
+    Right(u16), // Degrees
     Forward(u16),
 }
 
-#[derive(Debug, Clone)]
-enum Orientation {
-    North,
-    East,
-    South,
-    West,
-}
+/// Degrees anticlockwise from east, always below 360
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct Heading(u16);
 
 #[derive(Debug)]
 struct Ship {
     pub x: i32,
     pub y: i32,
-    orient: Orientation,
+    heading: Heading,
 }
 
 #[derive(Debug)]
 struct Waypoint {
     x: i32,
     y: i32,
+    /// Turns not yet applied, as they can add up to a quarter turn later
+    turn: Heading,
     ship: Ship,
 }
 
@@ -59,9 +58,8 @@ impl TryFrom<&str> for Action {
             Some('E') => Ok(Self::East(amount)),
             Some('W') => Ok(Self::West(amount)),
             Some('F') => Ok(Self::Forward(amount)),
-            // One step left or right is 90 degrees, and this wraps
-            Some('L') => Ok(Self::Left(((amount / 90) % 4).try_into()?)),
-            Some('R') => Ok(Self::Right(((amount / 90) % 4).try_into()?)),
+            Some('L') => Ok(Self::Left(amount)),
+            Some('R') => Ok(Self::Right(amount)),
             Some(x) => Err(anyhow!("First letter ({x}) is not a valid action")),
             None => Err(anyhow!("No first character.")),
         }
@@ -77,31 +75,26 @@ impl Action {
     }
 }
 
-impl Orientation {
-    fn turn_left(&mut self, count: u8) {
-        let mut result = self.clone();
-        for _ in 0..count {
-            result = match result {
-                Self::East => Self::North,
-                Self::North => Self::West,
-                Self::West => Self::South,
-                Self::South => Self::East,
-            }
-        }
-        *self = result;
+impl Heading {
+    fn left(self, degrees: u16) -> Self {
+        Self((self.0 + degrees % 360) % 360)
     }
 
-    fn turn_right(&mut self, count: u8) {
-        let mut result = self.clone();
-        for _ in 0..count {
-            result = match result {
-                Self::East => Self::South,
-                Self::South => Self::West,
-                Self::West => Self::North,
-                Self::North => Self::East,
-            }
+    fn right(self, degrees: u16) -> Self {
+        self.left(360 - degrees % 360)
+    }
+
+    /// Cosine and sine, exactly. Only quarter turns have them as whole
+    /// numbers, and no other whole number of degrees even has both rational
+    /// (Niven's theorem), so anything else can't keep positions exact.
+    fn cos_sin(self) -> anyhow::Result<(i32, i32)> {
+        match self.0 {
+            0 => Ok((1, 0)),
+            90 => Ok((0, 1)),
+            180 => Ok((-1, 0)),
+            270 => Ok((0, -1)),
+            degrees => bail!("Can't move at {degrees} degrees exactly, only at multiples of 90"),
         }
-        *self = result;
     }
 }
 
@@ -110,40 +103,40 @@ impl Default for Ship {
         Self {
             x: 0,
             y: 0,
-            orient: Orientation::East,
+            heading: Heading(0),
         }
     }
 }
 
 impl Ship {
-    fn forward_direction(&self, forward: u16) -> Action {
-        match self.orient {
-            Orientation::East => Action::East(forward),
-            Orientation::North => Action::North(forward),
-            Orientation::West => Action::West(forward),
-            Orientation::South => Action::South(forward),
-        }
-    }
-
-    fn apply(&mut self, act: Action) {
+    /// Turns add up freely, but moving forward needs a heading that's a
+    /// whole number of quarter turns
+    fn apply(&mut self, act: Action) -> anyhow::Result<()> {
         match act {
-            Action::Forward(x) => self.apply(self.forward_direction(x)),
-            Action::Left(x) => self.orient.turn_left(x),
-            Action::Right(x) => self.orient.turn_right(x),
+            Action::Forward(x) => {
+                let (cos, sin) = self.heading.cos_sin()?;
+                self.x += cos * x as i32;
+                self.y += sin * x as i32;
+            }
+            Action::Left(x) => self.heading = self.heading.left(x),
+            Action::Right(x) => self.heading = self.heading.right(x),
             Action::North(x) => self.y += x as i32,
             Action::South(x) => self.y -= x as i32,
             Action::East(x) => self.x += x as i32,
             Action::West(x) => self.x -= x as i32,
         }
+        Ok(())
     }
 
-    fn apply_iter<I>(&mut self, list: I)
+    fn apply_iter<I>(&mut self, list: I) -> anyhow::Result<()>
     where
         I: IntoIterator<Item = Action>,
     {
-        for act in list.into_iter() {
-            self.apply(act);
+        for (idx, act) in list.into_iter().enumerate() {
+            self.apply(act)
+                .map_err(|e| anyhow!("Line {}: {e}", idx + 1))?;
         }
+        Ok(())
     }
 
     fn manhattan_distance(&self) -> i32 {
@@ -156,6 +149,7 @@ impl Default for Waypoint {
         Self {
             x: 10,
             y: 1,
+            turn: Heading(0),
             ship: Ship::default(),
         }
     }
@@ -167,37 +161,43 @@ impl Waypoint {
         self.ship.y += self.y * count;
     }
 
-    fn rotate_clockwise(&mut self, count: i32) {
-        for _ in 0..count {
-            let new_x = self.y;
-            self.y = -self.x;
-            self.x = new_x;
-        }
-    }
-
-    fn rotate_counterclockwise(&mut self, count: i32) {
-        self.rotate_clockwise(4 - (count % 4));
+    /// Rotates by the turns so far, which must add up to a whole number of
+    /// quarter turns
+    fn settle(&mut self) -> anyhow::Result<()> {
+        let (cos, sin) = self.turn.cos_sin()?;
+        (self.x, self.y) = (self.x * cos - self.y * sin, self.x * sin + self.y * cos);
+        self.turn = Heading(0);
+        Ok(())
     }
 
-    fn apply(&mut self, act: Action) {
+    fn apply(&mut self, act: Action) -> anyhow::Result<()> {
         match act {
-            Action::North(x) => self.y += x as i32,
-            Action::South(x) => self.y -= x as i32,
-            Action::East(x) => self.x += x as i32,
-            Action::West(x) => self.x -= x as i32,
-            Action::Forward(x) => self.ship_forward(x as i32),
-            Action::Right(x) => self.rotate_clockwise((x % 4) as i32),
-            Action::Left(x) => self.rotate_counterclockwise(x as i32),
+            Action::Right(x) => self.turn = self.turn.right(x),
+            Action::Left(x) => self.turn = self.turn.left(x),
+            act => {
+                self.settle()?;
+                match act {
+                    Action::North(x) => self.y += x as i32,
+                    Action::South(x) => self.y -= x as i32,
+                    Action::East(x) => self.x += x as i32,
+                    Action::West(x) => self.x -= x as i32,
+                    Action::Forward(x) => self.ship_forward(x as i32),
+                    Action::Right(_) | Action::Left(_) => unreachable!(),
+                }
+            }
         }
+        Ok(())
     }
 
-    fn apply_iter<I>(&mut self, list: I)
+    fn apply_iter<I>(&mut self, list: I) -> anyhow::Result<()>
     where
         I: IntoIterator<Item = Action>,
     {
-        for act in list.into_iter() {
-            self.apply(act);
+        for (idx, act) in list.into_iter().enumerate() {
+            self.apply(act)
+                .map_err(|e| anyhow!("Line {}: {e}", idx + 1))?;
         }
+        Ok(())
     }
 
     fn manhattan_distance(&self) -> i32 {
@@ -212,14 +212,55 @@ mod tests {
     #[test]
     fn part1() {
         let mut ship = Ship::default();
-        ship.apply_iter(Action::from_file("test-input").unwrap());
+        ship.apply_iter(Action::from_file("test-input").unwrap())
+            .unwrap();
         assert_eq!(ship.manhattan_distance(), 25);
     }
 
     #[test]
     fn part2() {
         let mut waypoint = Waypoint::default();
-        waypoint.apply_iter(Action::from_file("test-input").unwrap());
+        waypoint
+            .apply_iter(Action::from_file("test-input").unwrap())
+            .unwrap();
         assert_eq!(waypoint.manhattan_distance(), 286);
     }
+
+    fn actions(text: &[&str]) -> Vec<Action> {
+        text.iter()
+            .map(|act| Action::try_from(*act).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn arbitrary_angles() {
+        // Half turns add up, and 720 degrees of turning is no turn at all
+        let mut ship = Ship::default();
+        ship.apply_iter(actions(&["L45", "L45", "F10", "R400", "R320", "F3"]))
+            .unwrap();
+        assert_eq!((ship.x, ship.y), (0, 13));
+
+        let mut waypoint = Waypoint::default();
+        waypoint
+            .apply_iter(actions(&["R30", "L10", "R70", "F2"]))
+            .unwrap();
+        assert_eq!((waypoint.ship.x, waypoint.ship.y), (2, -20));
+    }
+
+    #[test]
+    fn inexact_angles() {
+        let mut ship = Ship::default();
+        let e = ship
+            .apply_iter(actions(&["N3", "L45", "N1", "F10"]))
+            .unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Line 4: Can't move at 45 degrees exactly, only at multiples of 90"
+        );
+
+        // Turning is fine, until the waypoint has to be used
+        let mut waypoint = Waypoint::default();
+        waypoint.apply_iter(actions(&["R30"])).unwrap();
+        assert!(waypoint.apply(Action::North(1)).is_err());
+    }
 }
@@ -0,0 +1,159 @@
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+};
+
+use crate::BagMap;
+
+/// Which part of the rule graph to draw
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subgraph<'a> {
+    /// Every rule
+    Whole,
+    /// The bag and everything it can eventually hold
+    From(&'a str),
+    /// The bag and everything that can eventually hold it
+    To(&'a str),
+}
+
+impl Subgraph<'_> {
+    fn focus(&self) -> Option<&str> {
+        match self {
+            Self::Whole => None,
+            Self::From(name) | Self::To(name) => Some(name),
+        }
+    }
+}
+
+/// Outer bag, count, inner bag
+type Edge<'a> = (&'a str, u32, &'a str);
+
+impl BagMap {
+    /// Sorted nodes and edges of a subgraph
+    fn subgraph<'a>(&'a self, view: Subgraph<'a>) -> (Vec<&'a str>, Vec<Edge<'a>>) {
+        let mut nodes: Vec<&str> = match view {
+            Subgraph::Whole => self
+                .hmap
+                .iter()
+                .flat_map(|(outer, contains)| {
+                    std::iter::once(outer.as_str())
+                        .chain(contains.iter().map(|(_, inner)| inner.as_str()))
+                })
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect(),
+            Subgraph::From(name) => [name].into_iter().chain(self.contents(name)).collect(),
+            Subgraph::To(name) => [name].into_iter().chain(self.containers(name)).collect(),
+        };
+        nodes.sort_unstable();
+
+        let mut edges: Vec<_> = nodes
+            .iter()
+            .filter_map(|outer| self.hmap.get_key_value(*outer))
+            .flat_map(|(outer, contains)| {
+                contains
+                    .iter()
+                    .filter(|(_, inner)| nodes.binary_search(&inner.as_str()).is_ok())
+                    .map(move |(count, inner)| (outer.as_str(), *count, inner.as_str()))
+            })
+            .collect();
+        edges.sort_unstable();
+
+        (nodes, edges)
+    }
+
+    /// Writes the rules as a Graphviz digraph, pointing from outer to inner bags
+    pub fn write_dot<W: Write>(&self, out: &mut W, view: Subgraph) -> io::Result<()> {
+        let (nodes, edges) = self.subgraph(view);
+
+        writeln!(out, "digraph bags {{")?;
+        for node in nodes {
+            if view.focus() == Some(node) {
+                writeln!(out, "    \"{node}\" [style=filled, fillcolor=gold];")?;
+            } else {
+                writeln!(out, "    \"{node}\";")?;
+            }
+        }
+        for (outer, count, inner) in edges {
+            writeln!(out, "    \"{outer}\" -> \"{inner}\" [label=\"{count}\"];")?;
+        }
+        writeln!(out, "}}")
+    }
+
+    /// Writes the rules as a Mermaid flowchart, pointing from outer to inner bags
+    pub fn write_mermaid<W: Write>(&self, out: &mut W, view: Subgraph) -> io::Result<()> {
+        let (nodes, edges) = self.subgraph(view);
+        // Mermaid ids cannot contain spaces, so number the nodes instead
+        let id = |name: &str| nodes.binary_search(&name).unwrap();
+
+        writeln!(out, "flowchart LR")?;
+        for (idx, node) in nodes.iter().enumerate() {
+            writeln!(out, "    n{idx}[\"{node}\"]")?;
+        }
+        for (outer, count, inner) in &edges {
+            writeln!(out, "    n{} -->|{count}| n{}", id(outer), id(inner))?;
+        }
+        if let Some(focus) = view.focus() {
+            writeln!(out, "    style n{} fill:#ffd700", id(focus))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BagRule;
+
+    #[test]
+    fn dot_to() {
+        let mut out = Vec::new();
+        BagMap::from(BagRule::from_file("test-input").unwrap())
+            .write_dot(&mut out, Subgraph::To("bright white"))
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"digraph bags {
+    "bright white" [style=filled, fillcolor=gold];
+    "dark orange";
+    "light red";
+    "dark orange" -> "bright white" [label="3"];
+    "light red" -> "bright white" [label="1"];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn mermaid_from() {
+        let mut out = Vec::new();
+        BagMap::from(BagRule::from_file("test-input").unwrap())
+            .write_mermaid(&mut out, Subgraph::From("shiny gold"))
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"flowchart LR
+    n0["dark olive"]
+    n1["dotted black"]
+    n2["faded blue"]
+    n3["shiny gold"]
+    n4["vibrant plum"]
+    n0 -->|3| n2
+    n0 -->|4| n1
+    n3 -->|1| n0
+    n3 -->|2| n4
+    n4 -->|5| n2
+    n4 -->|6| n1
+    style n3 fill:#ffd700
+"#
+        );
+    }
+
+    #[test]
+    fn whole_graph() {
+        let bags = BagMap::from(BagRule::from_file("test-input").unwrap());
+        let (nodes, edges) = bags.subgraph(Subgraph::Whole);
+        assert_eq!(nodes.len(), 9);
+        assert_eq!(edges.len(), 13);
+    }
+}
@@ -1,27 +1,93 @@
-use lazy_static::lazy_static;
-use regex::Regex;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
+    env,
     error::Error,
+    fmt::{self, Display, Formatter},
     fs::File,
-    io::{BufRead, BufReader},
+    io::{self, BufRead, BufReader},
     str::FromStr,
 };
 
-fn main() {
-    let rules = BagRule::from_file("input").unwrap();
-    println!("{}", BagRule::num_contain("shiny gold", &rules));
-    println!(
-        "{}",
-        BagMap::from(rules).bags_contained("shiny gold").unwrap()
-    );
+mod export;
+mod query;
+use export::Subgraph;
+
+/// Usage: `day7 [dot|mermaid [from|to <bag>]]` or `day7 query <query>`, where
+/// the query is one of `paths <from> <to>`, `depth <bag>`, `topo` or `roots`
+fn main() -> Result<(), Box<dyn Error>> {
+    let bags = BagMap::from(BagRule::from_file("input")?);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args[..] {
+        [] => {
+            println!("{}", bags.containers("shiny gold").len());
+            println!("{}", bags.bags_contained("shiny gold")?);
+        }
+        ["query", ref query @ ..] => match query {
+            ["paths", from, to] => {
+                for path in bags.paths(from, to)? {
+                    println!("{} ({})", path.bags.join(" -> "), path.count);
+                }
+            }
+            ["depth", bag] => println!("{}", bags.max_depth(bag)?),
+            ["topo"] => bags
+                .topological_order()?
+                .into_iter()
+                .for_each(|bag| println!("{bag}")),
+            ["roots"] => bags
+                .outermost()
+                .into_iter()
+                .for_each(|bag| println!("{bag}")),
+            _ => {
+                return Err("Expected `paths <from> <to>`, `depth <bag>`, `topo` or `roots`".into())
+            }
+        },
+        [format, ref view @ ..] => {
+            let view = match view {
+                [] => Subgraph::Whole,
+                ["from", bag] => Subgraph::From(bag),
+                ["to", bag] => Subgraph::To(bag),
+                _ => return Err("Expected `from <bag>` or `to <bag>`".into()),
+            };
+            let mut out = io::stdout().lock();
+            match format {
+                "dot" => bags.write_dot(&mut out, view)?,
+                "mermaid" => bags.write_mermaid(&mut out, view)?,
+                _ => return Err(format!("Unknown output format \"{format}\"").into()),
+            }
+        }
+    }
+    Ok(())
 }
 
 #[derive(Debug)]
 struct BagMap {
     hmap: HashMap<String, Box<[(u32, String)]>>,
+    /// Reverse adjacency: bag name -> names of the bags that directly hold it
+    contained_in: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum BagError {
+    UnknownBag(String),
+    /// Bags forming a containment cycle, in containment order
+    Cycle(Vec<String>),
+    Overflow(String),
+}
+
+impl Display for BagError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownBag(name) => write!(f, "No rule for bag \"{name}\""),
+            Self::Cycle(names) => write!(f, "Bags contain each other: {}", names.join(" -> ")),
+            Self::Overflow(name) => write!(f, "Bag count overflowed inside \"{name}\""),
+        }
+    }
 }
 
+impl Error for BagError {}
+
 #[derive(Debug, PartialEq, Clone)]
 struct BagRule {
     name: String,
@@ -31,91 +97,82 @@ struct BagRule {
 impl FromStr for BagRule {
     type Err = Box<dyn Error>;
 
+    /// Parses `<color> bags contain <n> <color> bag(s), ... .`, where colors
+    /// are any number of space-separated words
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        lazy_static! {
-            static ref BAG: Regex = Regex::new(r#"(\w+ \w+) bags contain "#).unwrap();
-            static ref CONTAINED: Regex = Regex::new(r#"(\d+) (\w+ \w+) bags?(?:,|\.)"#).unwrap();
-        }
+        let (name, contents) = s
+            .split_once(" bags contain ")
+            .ok_or("Malformed input: expected \"<color> bags contain\"")?;
+        let name = parse_color(name)?;
+
+        let contents = contents
+            .strip_suffix('.')
+            .ok_or_else(|| format!("Malformed input: \"{contents}\" does not end with '.'"))?;
 
-        let name = BAG
-            .captures(s)
-            .ok_or("Malformed input: no bag name")?
-            .get(1)
-            .ok_or("Malformed input: no bag name")?;
-
-        let mut parse_sentinel: Result<(), Box<dyn Error>> = Ok(());
-        let contains = CONTAINED
-            .captures_iter(&s[name.end()..])
-            .map_while(|cap| {
-                let num = match cap[1].parse::<u32>() {
-                    Ok(num) => num,
-                    Err(e) => {
-                        parse_sentinel = Err(Box::new(e));
-                        return None;
+        let contains = if contents == "no other bags" {
+            Box::new([]) as Box<[_]>
+        } else {
+            contents
+                .split(", ")
+                .map(|bag| {
+                    let (count, rest) = bag
+                        .split_once(' ')
+                        .ok_or_else(|| format!("Malformed input: no count in \"{bag}\""))?;
+                    let count = count
+                        .parse::<u32>()
+                        .map_err(|e| format!("Malformed input: bad count in \"{bag}\": {e}"))?;
+                    let color = match count {
+                        1 => rest.strip_suffix(" bag"),
+                        _ => rest.strip_suffix(" bags"),
                     }
-                };
-                Some((num, String::from(&cap[2])))
-            })
-            .collect();
-        parse_sentinel?;
+                    .ok_or_else(|| {
+                        format!("Malformed input: \"{bag}\" does not end with \"bag(s)\"")
+                    })?;
+                    Ok((count, parse_color(color)?))
+                })
+                .collect::<Result<_, Box<dyn Error>>>()?
+        };
 
-        Ok(Self {
-            name: name.as_str().into(),
-            contains,
-        })
+        Ok(Self { name, contains })
+    }
+}
+
+/// A color is one or more words of letters or digits, separated by single spaces
+fn parse_color(s: &str) -> Result<String, Box<dyn Error>> {
+    let valid_word = |word: &str| !word.is_empty() && word.chars().all(char::is_alphanumeric);
+    if s.split(' ').all(valid_word) {
+        Ok(s.into())
+    } else {
+        Err(format!("Malformed input: \"{s}\" is not a bag color").into())
+    }
+}
+
+impl Display for BagRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bags contain ", self.name)?;
+        if self.contains.is_empty() {
+            return write!(f, "no other bags.");
+        }
+        for (idx, (count, color)) in self.contains.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            let plural = if *count == 1 { "" } else { "s" };
+            write!(f, "{count} {color} bag{plural}")?;
+        }
+        write!(f, ".")
     }
 }
 
 impl BagRule {
     fn from_file(name: &str) -> Result<Vec<BagRule>, Box<dyn Error>> {
-        let mut build_sentinel: Result<(), Box<dyn Error>> = Ok(());
-        let bufs = BufReader::new(File::open(name).unwrap())
+        BufReader::new(File::open(name)?)
             .lines()
-            .map_while(|line| match BagRule::from_str(&line.unwrap()) {
-                Ok(rule) => Some(rule),
-                Err(e) => {
-                    build_sentinel = Err(e);
-                    None
-                }
+            .enumerate()
+            .map(|(idx, line)| {
+                BagRule::from_str(&line?).map_err(|e| format!("Line {}: {e}", idx + 1).into())
             })
-            .collect();
-        build_sentinel?;
-        Ok(bufs)
-    }
-
-    fn num_contain(name: &str, rules: &[BagRule]) -> usize {
-        let mut names = vec![String::from(name)];
-        let update_rules = |cur_rules: &[BagRule], remove: &[String]| -> Vec<BagRule> {
-            cur_rules
-                .iter()
-                .filter(|rule| !(remove.contains(&rule.name)))
-                .cloned()
-                .collect()
-        };
-
-        let search = |cur_rules: &[BagRule], names: &[String]| -> Vec<String> {
-            cur_rules
-                .iter()
-                .filter_map(|rule| {
-                    rule.contains
-                        .iter()
-                        .find(|(_, inside)| names.contains(inside))
-                        .map(|_| String::from(&rule.name))
-                })
-                .collect()
-        };
-
-        let mut rules_remaining = update_rules(rules, &names);
-        let mut found = search(&rules_remaining, &names);
-
-        let mut count = 0;
-        while !found.is_empty() {
-            count += found.len();
-            names.append(&mut found);
-            rules_remaining = update_rules(&rules_remaining, &names);
-            found.extend(search(&rules_remaining, &names));
-        }
-        count
+            .collect()
     }
 }
 
@@ -124,23 +181,124 @@ where
     I: IntoIterator<Item = BagRule>,
 {
     fn from(value: I) -> Self {
-        BagMap {
-            hmap: value
-                .into_iter()
-                .map(|rule| (rule.name, rule.contains))
-                .collect(),
+        let hmap: HashMap<_, _> = value
+            .into_iter()
+            .map(|rule| (rule.name, rule.contains))
+            .collect();
+
+        let mut contained_in: HashMap<String, Vec<String>> = HashMap::new();
+        for (outer, contains) in &hmap {
+            for (_, inner) in contains.iter() {
+                contained_in
+                    .entry(inner.clone())
+                    .or_default()
+                    .push(outer.clone());
+            }
         }
+
+        BagMap { hmap, contained_in }
     }
 }
 
 impl BagMap {
-    fn bags_contained(&self, name: &str) -> Option<u32> {
-        let mut sum = 0;
-        for (count, name) in self.hmap.get(name)?.as_ref() {
-            sum += count * (1 + self.bags_contained(name)?);
+    /// All bags that can eventually contain `name`, in breadth-first order
+    fn containers(&self, name: &str) -> Vec<&str> {
+        let mut seen = HashSet::from([name]);
+        let mut queue = VecDeque::from([name]);
+        let mut found = Vec::new();
+
+        while let Some(cur) = queue.pop_front() {
+            for outer in self.contained_in.get(cur).into_iter().flatten() {
+                if seen.insert(outer) {
+                    found.push(outer.as_str());
+                    queue.push_back(outer);
+                }
+            }
         }
-        Some(sum)
+        found
     }
+
+    /// All bags that `name` can eventually hold, in breadth-first order
+    fn contents(&self, name: &str) -> Vec<&str> {
+        let mut seen = HashSet::from([name]);
+        let mut queue = VecDeque::from([name]);
+        let mut found = Vec::new();
+
+        while let Some(cur) = queue.pop_front() {
+            for (_, inner) in self.hmap.get(cur).into_iter().flatten() {
+                if seen.insert(inner) {
+                    found.push(inner.as_str());
+                    queue.push_back(inner);
+                }
+            }
+        }
+        found
+    }
+
+    /// Total number of bags inside `name`
+    fn bags_contained(&self, name: &str) -> Result<u128, BagError> {
+        self.fold_inside(name, |cur, contains, memo: &HashMap<_, u128>| {
+            contains.iter().try_fold(0u128, |sum, (count, inner)| {
+                memo[inner.as_str()]
+                    .checked_add(1)
+                    .and_then(|per_bag| per_bag.checked_mul(u128::from(*count)))
+                    .and_then(|total| sum.checked_add(total))
+                    .ok_or_else(|| BagError::Overflow(cur.into()))
+            })
+        })
+    }
+
+    /// Computes a value for `name` from the values of the bags directly in it.
+    ///
+    /// Walks the rules depth-first with an explicit stack, so each bag is only
+    /// combined once no matter how many paths lead to it.
+    fn fold_inside<T, F>(&self, name: &str, mut combine: F) -> Result<T, BagError>
+    where
+        T: Copy,
+        F: FnMut(&str, &[(u32, String)], &HashMap<&str, T>) -> Result<T, BagError>,
+    {
+        let mut memo: HashMap<&str, T> = HashMap::new();
+        // Bags currently being expanded, with the index of the next child
+        let mut stack: Vec<(&str, usize)> = vec![(name, 0)];
+        let mut on_stack: HashSet<&str> = HashSet::from([name]);
+
+        while let Some((cur, child_idx)) = stack.last_mut() {
+            let contains = self
+                .hmap
+                .get(*cur)
+                .ok_or_else(|| BagError::UnknownBag(String::from(*cur)))?;
+
+            if let Some((_, inner)) = contains.get(*child_idx) {
+                *child_idx += 1;
+                let inner = inner.as_str();
+                if memo.contains_key(inner) {
+                    continue;
+                }
+                if !on_stack.insert(inner) {
+                    return Err(cycle_error(&stack, inner));
+                }
+                stack.push((inner, 0));
+            } else {
+                let cur = *cur;
+                let value = combine(cur, contains, &memo)?;
+                memo.insert(cur, value);
+                on_stack.remove(cur);
+                stack.pop();
+            }
+        }
+        Ok(memo[name])
+    }
+}
+
+/// The loop from `inner`'s place on a depth-first stack back round to it
+fn cycle_error(stack: &[(&str, usize)], inner: &str) -> BagError {
+    let mut cycle: Vec<_> = stack
+        .iter()
+        .skip_while(|(name, _)| *name != inner)
+        .map(|(name, _)| String::from(*name))
+        .collect();
+    cycle.push(inner.into());
+    BagError::Cycle(cycle)
 }
 
 #[cfg(test)]
@@ -164,6 +322,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_long_colors() {
+        let rule = BagRule::from_str(
+            "very light red bags contain 1 gold bag, 12 pale sea foam green bags.",
+        )
+        .unwrap();
+        assert_eq!(
+            rule,
+            BagRule {
+                name: "very light red".into(),
+                contains: Box::new([
+                    (1, String::from("gold")),
+                    (12, String::from("pale sea foam green"))
+                ])
+            }
+        );
+    }
+
+    #[test]
+    fn parse_rejects_garbage() {
+        for line in [
+            "light red bags contain 1 bright white bag, 2 muted yellow bags. extra",
+            "light red bags contain 1 bright white bag, 2 muted yellow bags",
+            "light red bags contain 1 bright white bag; 2 muted yellow bags.",
+            "light red bags contain 2 bright white bag.",
+            "light red bags contain one bright white bag.",
+            "light red bags contain no other bags, 1 muted yellow bag.",
+            " bags contain no other bags.",
+            "light  red bags contain no other bags.",
+            "light red bag contains no other bags.",
+        ] {
+            assert!(BagRule::from_str(line).is_err(), "{line}");
+        }
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for rule in BagRule::from_file("test-input").unwrap() {
+            assert_eq!(BagRule::from_str(&rule.to_string()).unwrap(), rule);
+        }
+        let rule = BagRule::from_str("faded blue bags contain no other bags.").unwrap();
+        assert_eq!(rule.to_string(), "faded blue bags contain no other bags.");
+    }
+
     #[test]
     fn parse_file() {
         let rules = BagRule::from_file("test-input").unwrap();
@@ -189,7 +391,19 @@ mod tests {
     #[test]
     fn part1() {
         let rules = BagRule::from_file("test-input").unwrap();
-        assert_eq!(BagRule::num_contain("shiny gold", &rules), 4);
+        assert_eq!(BagMap::from(rules).containers("shiny gold").len(), 4);
+    }
+
+    #[test]
+    fn containers_list() {
+        let bags = BagMap::from(BagRule::from_file("test-input").unwrap());
+        let mut found = bags.containers("shiny gold");
+        found.sort_unstable();
+        assert_eq!(
+            found,
+            ["bright white", "dark orange", "light red", "muted yellow"]
+        );
+        assert!(bags.containers("light red").is_empty());
     }
 
     #[test]
@@ -200,4 +414,64 @@ mod tests {
             126
         );
     }
+
+    #[test]
+    fn contained_cycle() {
+        let rules = [
+            "dark red bags contain 1 dark green bag.",
+            "dark green bags contain 2 dark blue bags.",
+            "dark blue bags contain 1 dark red bag, 3 dark white bags.",
+            "dark white bags contain no other bags.",
+        ]
+        .map(|line| BagRule::from_str(line).unwrap());
+        let bags = BagMap::from(rules);
+        assert_eq!(
+            bags.bags_contained("dark red"),
+            Err(BagError::Cycle(vec![
+                "dark red".into(),
+                "dark green".into(),
+                "dark blue".into(),
+                "dark red".into()
+            ]))
+        );
+        assert_eq!(bags.bags_contained("dark white"), Ok(0));
+        assert_eq!(
+            bags.bags_contained("dark black"),
+            Err(BagError::UnknownBag("dark black".into()))
+        );
+    }
+
+    #[test]
+    fn contained_diamond() {
+        // Each layer holds two copies of two bags that both hold the next
+        // layer, so the naive recursion would visit 2^60 paths.
+        let mut rules: Vec<_> = (0..60)
+            .flat_map(|layer| {
+                let next = layer + 1;
+                [
+                    format!("a {layer} bags contain 2 b {layer} bags, 2 c {layer} bags."),
+                    format!("b {layer} bags contain 1 a {next} bag."),
+                    format!("c {layer} bags contain 1 a {next} bag."),
+                ]
+            })
+            .collect();
+        rules.push("a 60 bags contain no other bags.".into());
+        let bags = BagMap::from(rules.iter().map(|line| BagRule::from_str(line).unwrap()));
+
+        // n(a 60) = 0, n(a k) = 4 * (2 + n(a k+1))
+        let expected = (0..60).fold(0u128, |acc, _| 4 * (2 + acc));
+        assert_eq!(bags.bags_contained("a 0"), Ok(expected));
+    }
+
+    #[test]
+    fn contained_long_chain() {
+        // Deep enough that checking the whole stack for cycles at every step
+        // would take minutes
+        let mut rules: Vec<_> = (0..100_000)
+            .map(|idx| format!("x {idx} bags contain 1 x {} bag.", idx + 1))
+            .collect();
+        rules.push("x 100000 bags contain no other bags.".into());
+        let bags = BagMap::from(rules.iter().map(|line| BagRule::from_str(line).unwrap()));
+        assert_eq!(bags.bags_contained("x 0"), Ok(100_000));
+    }
 }
@@ -0,0 +1,221 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{cycle_error, BagError, BagMap};
+
+/// One way of nesting bags, from the outermost to the innermost
+#[derive(Debug, PartialEq, Eq)]
+pub struct BagPath<'a> {
+    pub bags: Vec<&'a str>,
+    /// Number of innermost bags reached along this path
+    pub count: u128,
+}
+
+impl BagMap {
+    /// Every containment path from `from` down to `to`, with the counts
+    /// multiplied along the way
+    pub fn paths<'a>(&'a self, from: &'a str, to: &str) -> Result<Vec<BagPath<'a>>, BagError> {
+        if !self.hmap.contains_key(from) {
+            return Err(BagError::UnknownBag(from.into()));
+        }
+
+        let mut found = Vec::new();
+        // Current path, with the index of the next child of each bag on it
+        let mut stack: Vec<(&str, usize)> = vec![(from, 0)];
+        let mut counts: Vec<u128> = vec![1];
+        let mut on_stack: HashSet<&str> = HashSet::from([from]);
+
+        while let Some((cur, child_idx)) = stack.last_mut() {
+            let contains = self.hmap.get(*cur).map_or(&[][..], |c| c);
+            let Some((count, inner)) = contains.get(*child_idx) else {
+                on_stack.remove(*cur);
+                stack.pop();
+                counts.pop();
+                continue;
+            };
+            *child_idx += 1;
+
+            let inner = inner.as_str();
+            if on_stack.contains(inner) {
+                return Err(cycle_error(&stack, inner));
+            }
+
+            let total = counts
+                .last()
+                .unwrap()
+                .checked_mul(u128::from(*count))
+                .ok_or_else(|| BagError::Overflow(inner.into()))?;
+            if inner == to {
+                found.push(BagPath {
+                    bags: stack.iter().map(|(name, _)| *name).chain([inner]).collect(),
+                    count: total,
+                });
+            } else {
+                on_stack.insert(inner);
+                stack.push((inner, 0));
+                counts.push(total);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Deepest level of nesting inside `name`, where an empty bag is depth 0
+    pub fn max_depth(&self, name: &str) -> Result<usize, BagError> {
+        self.fold_inside(name, |_, contains, memo: &HashMap<_, usize>| {
+            Ok(contains
+                .iter()
+                .map(|(_, inner)| memo[inner.as_str()] + 1)
+                .max()
+                .unwrap_or(0))
+        })
+    }
+
+    /// Every bag, each listed before all of the bags it can hold
+    pub fn topological_order(&self) -> Result<Vec<&str>, BagError> {
+        let mut holders: HashMap<&str, usize> =
+            self.hmap.keys().map(|name| (name.as_str(), 0)).collect();
+        for contains in self.hmap.values() {
+            for (_, inner) in contains.iter() {
+                *holders.entry(inner).or_default() += 1;
+            }
+        }
+
+        let mut ready: Vec<&str> = holders
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(name, _)| *name)
+            .collect();
+        ready.sort_unstable();
+        let mut ready = VecDeque::from(ready);
+
+        let mut order = Vec::with_capacity(holders.len());
+        while let Some(cur) = ready.pop_front() {
+            order.push(cur);
+            for (_, inner) in self.hmap.get(cur).into_iter().flatten() {
+                let count = holders.get_mut(inner.as_str()).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    ready.push_back(inner);
+                }
+            }
+        }
+
+        if order.len() == holders.len() {
+            Ok(order)
+        } else {
+            Err(self.find_cycle(&holders))
+        }
+    }
+
+    /// Walks upwards through bags that are still held by something until a
+    /// bag repeats, which must close a cycle
+    fn find_cycle(&self, holders: &HashMap<&str, usize>) -> BagError {
+        let blocked = |name: &str| holders[name] > 0;
+        let mut next = holders.keys().copied().filter(|name| blocked(name)).min();
+        let mut walked: Vec<&str> = Vec::new();
+
+        while let Some(cur) = next {
+            if let Some(pos) = walked.iter().position(|name| *name == cur) {
+                // Walked from inner to outer, so flip into containment order
+                let mut cycle: Vec<String> = walked[pos..]
+                    .iter()
+                    .rev()
+                    .map(|name| String::from(*name))
+                    .collect();
+                cycle.insert(0, cur.into());
+                return BagError::Cycle(cycle);
+            }
+            walked.push(cur);
+            next = self.contained_in[cur]
+                .iter()
+                .map(String::as_str)
+                .find(|name| blocked(name));
+        }
+        unreachable!("Every blocked bag is held by another blocked bag")
+    }
+
+    /// Bags that no other bag can hold, sorted by name
+    pub fn outermost(&self) -> Vec<&str> {
+        let mut found: Vec<_> = self
+            .hmap
+            .keys()
+            .filter(|name| !self.contained_in.contains_key(*name))
+            .map(String::as_str)
+            .collect();
+        found.sort_unstable();
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::BagRule;
+
+    #[test]
+    fn paths() {
+        let bags = BagMap::from(BagRule::from_file("test-input").unwrap());
+        let mut paths = bags.paths("light red", "shiny gold").unwrap();
+        paths.sort_unstable_by_key(|path| path.count);
+        assert_eq!(
+            paths,
+            [
+                BagPath {
+                    bags: vec!["light red", "bright white", "shiny gold"],
+                    count: 1,
+                },
+                BagPath {
+                    bags: vec!["light red", "muted yellow", "shiny gold"],
+                    count: 4,
+                },
+            ]
+        );
+        assert!(bags.paths("shiny gold", "light red").unwrap().is_empty());
+    }
+
+    #[test]
+    fn depth() {
+        let bags = BagMap::from(BagRule::from_file("test-input-pt2").unwrap());
+        assert_eq!(bags.max_depth("shiny gold"), Ok(6));
+        assert_eq!(bags.max_depth("dark violet"), Ok(0));
+    }
+
+    #[test]
+    fn topological() {
+        let bags = BagMap::from(BagRule::from_file("test-input").unwrap());
+        let order = bags.topological_order().unwrap();
+        assert_eq!(order.len(), 9);
+        let pos = |name| order.iter().position(|bag| *bag == name).unwrap();
+        for (outer, contains) in &bags.hmap {
+            for (_, inner) in contains.iter() {
+                assert!(pos(outer) < pos(inner), "{outer} before {inner}");
+            }
+        }
+    }
+
+    #[test]
+    fn topological_cycle() {
+        let rules = [
+            "dark red bags contain 1 dark green bag.",
+            "dark green bags contain 2 dark blue bags.",
+            "dark blue bags contain 1 dark green bag, 3 dark white bags.",
+            "dark white bags contain no other bags.",
+        ]
+        .map(|line| BagRule::from_str(line).unwrap());
+        assert_eq!(
+            BagMap::from(rules).topological_order(),
+            Err(BagError::Cycle(vec![
+                "dark blue".into(),
+                "dark green".into(),
+                "dark blue".into()
+            ]))
+        );
+    }
+
+    #[test]
+    fn roots() {
+        let bags = BagMap::from(BagRule::from_file("test-input").unwrap());
+        assert_eq!(bags.outermost(), ["dark orange", "light red"]);
+    }
+}
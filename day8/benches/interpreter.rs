@@ -0,0 +1,68 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use day8_lib::{
+    asm,
+    compiled::Compiled,
+    machine::{Machine, Program},
+};
+
+/// A million straight-line instructions that exit
+fn straight_line() -> Program {
+    let lines = (0..1_000_000).map(|idx| match idx % 4 {
+        0 => "acc +3",
+        1 => "nop +7",
+        2 => "jmp +1",
+        _ => "acc -1",
+    });
+    Program::from(asm::assemble(lines).unwrap())
+}
+
+/// A short loop counted down from a register, run for millions of steps
+fn counted_loop() -> Program {
+    let lines = [
+        "set b +1000000",
+        "loop:",
+        "acc +2",
+        "mul c +3",
+        "seq c +0",
+        "acc -1",
+        "acc b -1",
+        "jnz b loop",
+    ];
+    Program::from(asm::assemble(lines).unwrap())
+}
+
+fn bench_program(c: &mut Criterion, name: &str, program: &Program) {
+    let mut group = c.benchmark_group(name);
+    group.sample_size(20);
+    group.bench_function("machine", |b| {
+        b.iter_batched_ref(
+            || Machine::new(program),
+            |machine| machine.run(program, false, usize::MAX),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("compiled", |b| {
+        let compiled = Compiled::new(program, &Machine::new(program));
+        b.iter_batched_ref(
+            || Machine::new(program),
+            |machine| compiled.run(machine, false, usize::MAX),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("compile_and_run", |b| {
+        b.iter_batched_ref(
+            || Machine::new(program),
+            |machine| Compiled::new(program, machine).run(machine, false, usize::MAX),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+fn interpreters(c: &mut Criterion) {
+    bench_program(c, "straight_line", &straight_line());
+    bench_program(c, "counted_loop", &counted_loop());
+}
+
+criterion_group!(benches, interpreters);
+criterion_main!(benches);
@@ -0,0 +1,175 @@
+use std::{collections::HashMap, fmt::Write, str::FromStr};
+
+use anyhow::{anyhow, bail};
+
+use crate::instruction::{Code, Instruction, REGISTER_NAMES};
+
+/// Starts a comment that runs to the end of the line
+const COMMENT: char = ';';
+
+fn is_label(word: &str) -> bool {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Whether the argument of `code` is a jump offset that may name a label.
+/// `nop` is included so that a flipped `jmp` keeps its target.
+fn takes_target(code: Code) -> bool {
+    matches!(code, Code::Jmp | Code::Jz | Code::Jnz | Code::Nop)
+}
+
+/// Assembles source with `label:` definitions, symbolic jump targets, blank
+/// lines and `;` comments into instructions with relative offsets
+pub fn assemble<I, S>(lines: I) -> anyhow::Result<Vec<Instruction>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    // First pass: strip labels and comments, noting where each label points
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    let mut bodies: Vec<(usize, String)> = Vec::new();
+    for (line_idx, line) in lines.into_iter().enumerate() {
+        let line = line.as_ref();
+        let mut body = line.split(COMMENT).next().unwrap_or_default().trim();
+
+        if let Some((label, rest)) = body.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                bail!("Line {}: invalid label \"{label}\"", line_idx + 1);
+            }
+            if labels.insert(label.into(), bodies.len()).is_some() {
+                bail!("Line {}: label \"{label}\" defined twice", line_idx + 1);
+            }
+            body = rest.trim();
+        }
+
+        if !body.is_empty() {
+            bodies.push((line_idx, body.into()));
+        }
+    }
+
+    // Second pass: swap labels for offsets and parse as plain instructions
+    bodies
+        .iter()
+        .enumerate()
+        .map(|(idx, (line_idx, body))| {
+            let with_line = |e: anyhow::Error| anyhow!("Line {}: {e}", line_idx + 1);
+            let (rest, arg) = body.rsplit_once(' ').unwrap_or(("", body));
+            let code = rest.split(' ').next().unwrap_or_default();
+
+            let arg = match Code::from_str(code) {
+                Ok(code) if takes_target(code) && is_label(arg) => {
+                    let target = *labels
+                        .get(arg)
+                        .ok_or_else(|| with_line(anyhow!("unknown label \"{arg}\"")))?;
+                    format!("{:+}", target as isize - idx as isize)
+                }
+                _ => arg.into(),
+            };
+            Instruction::from_str(&format!("{rest} {arg}")).map_err(with_line)
+        })
+        .collect()
+}
+
+/// Writes instructions back out as source, with a label at every jump target
+/// that lands inside the program or just past its end
+pub fn disassemble(instructions: &[Instruction]) -> String {
+    let target = |idx: usize, instruction: &Instruction| {
+        (matches!(instruction.code, Code::Jmp | Code::Jz | Code::Jnz))
+            .then(|| idx.checked_add_signed(instruction.arg))
+            .flatten()
+            .filter(|target| *target <= instructions.len())
+    };
+
+    let mut targets: Vec<usize> = instructions
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, instruction)| target(idx, instruction))
+        .collect();
+    targets.sort_unstable();
+    targets.dedup();
+    let label = |target: usize| format!("l{target}");
+
+    let mut out = String::new();
+    for idx in 0..=instructions.len() {
+        if targets.binary_search(&idx).is_ok() {
+            writeln!(out, "{}:", label(idx)).unwrap();
+        }
+        let Some(instruction) = instructions.get(idx) else {
+            break;
+        };
+
+        write!(out, "    {}", instruction.code.mnemonic()).unwrap();
+        if instruction.reg != 0 {
+            write!(out, " {}", REGISTER_NAMES[instruction.reg]).unwrap();
+        }
+        match target(idx, instruction) {
+            Some(target) => writeln!(out, " {}", label(target)),
+            None => writeln!(out, " {:+}", instruction.arg),
+        }
+        .unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels() {
+        let source = "
+            ; count b down from 3, adding 2 each time
+                set b +3
+            loop:
+                acc +2      ; a += 2
+                acc b -1
+                jnz b loop
+                jmp end
+            skipped: acc +100
+            end:
+        ";
+        let expected: Vec<Instruction> = [
+            "set b +3", "acc +2", "acc b -1", "jnz b -2", "jmp +2", "acc +100",
+        ]
+        .iter()
+        .map(|line| line.parse().unwrap())
+        .collect();
+        assert_eq!(assemble(source.lines()).unwrap(), expected);
+    }
+
+    #[test]
+    fn bad_labels() {
+        assert!(assemble(["a: nop +0", "a: nop +0"]).is_err());
+        assert!(assemble(["jmp nowhere"]).is_err());
+        assert!(assemble(["1a: nop +0"]).is_err());
+        assert!(assemble(["acc start", "start:"]).is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let source = std::fs::read_to_string("test-input").unwrap();
+        let program = assemble(source.lines()).unwrap();
+        let listing = disassemble(&program);
+        assert_eq!(
+            listing,
+            "    nop +0
+l1:
+    acc +1
+    jmp l6
+l3:
+    acc +3
+    jmp l1
+    acc -99
+l6:
+    acc +1
+    jmp l3
+    acc +6
+"
+        );
+        assert_eq!(assemble(listing.lines()).unwrap(), program);
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+
+use crate::instruction::Instruction;
+
+/// Control flow graph of a program, with node `len` standing for a clean exit.
+///
+/// Conditional codes get an edge for each branch, so for programs using them
+/// "reachable" and "can exit" mean "might".
+#[derive(Debug)]
+pub struct Cfg {
+    instructions: Vec<Instruction>,
+    /// Whether each instruction can be reached from the first one
+    reachable: Vec<bool>,
+    /// Whether each node, including the exit, has a path to the exit
+    exits: Vec<bool>,
+}
+
+impl Cfg {
+    pub fn new(instructions: &[Instruction]) -> Self {
+        let len = instructions.len();
+        // Jumps past the exit are left out, as nothing follows them
+        let successors: Vec<Vec<usize>> = instructions
+            .iter()
+            .enumerate()
+            .map(|(idx, instruction)| {
+                instruction
+                    .code
+                    .possible_offsets(instruction.arg)
+                    .into_iter()
+                    .filter_map(|offset| idx.checked_add_signed(offset))
+                    .filter(|next| *next <= len)
+                    .collect()
+            })
+            .collect();
+
+        let mut predecessors = vec![Vec::new(); len + 1];
+        for (idx, nexts) in successors.iter().enumerate() {
+            for next in nexts {
+                predecessors[*next].push(idx);
+            }
+        }
+
+        let mut reachable = Self::search(&successors, len + 1, 0);
+        reachable.truncate(len);
+        let exits = Self::search(&predecessors, len + 1, len);
+
+        Self {
+            instructions: instructions.to_vec(),
+            reachable,
+            exits,
+        }
+    }
+
+    /// Breadth-first search over `edges`, marking every node found from `start`
+    fn search(edges: &[Vec<usize>], num_nodes: usize, start: usize) -> Vec<bool> {
+        let mut found = vec![false; num_nodes];
+        found[start] = true;
+        let mut queue = VecDeque::from([start]);
+        while let Some(cur) = queue.pop_front() {
+            for next in edges.get(cur).into_iter().flatten() {
+                if !found[*next] {
+                    found[*next] = true;
+                    queue.push_back(*next);
+                }
+            }
+        }
+        found
+    }
+
+    /// Instructions that can never run
+    pub fn unreachable(&self) -> Vec<usize> {
+        (0..self.reachable.len())
+            .filter(|idx| !self.reachable[*idx])
+            .collect()
+    }
+
+    /// Instructions that, once run, can never lead to a clean exit
+    pub fn doomed(&self) -> Vec<usize> {
+        (0..self.instructions.len())
+            .filter(|idx| !self.exits[*idx])
+            .collect()
+    }
+
+    /// Every instruction whose flip (`jmp` <-> `nop`) makes a looping program
+    /// exit, found in linear time.
+    ///
+    /// A flipped instruction must run on the way to the loop, and the flipped
+    /// branch must have a path to the exit. That path can't come back through
+    /// the flipped instruction, since the original can't exit from there.
+    pub fn terminating_flips(&self) -> Vec<usize> {
+        let len = self.instructions.len();
+        self.instructions
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| self.reachable[*idx] && !self.exits[*idx])
+            .filter_map(|(idx, instruction)| {
+                let flipped = instruction.code.flipped()?;
+                // A jump before the first instruction never exits cleanly
+                let exits = flipped
+                    .possible_offsets(instruction.arg)
+                    .into_iter()
+                    .map(|offset| idx.checked_add_signed(offset))
+                    .all(|next| next.is_some_and(|next| next <= len && self.exits[next]));
+                exits.then_some(idx)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Console;
+
+    fn test_cfg() -> Cfg {
+        Cfg::new(&Console::from_file("test-input").unwrap().program)
+    }
+
+    #[test]
+    fn flips() {
+        assert_eq!(test_cfg().terminating_flips(), [7]);
+
+        // Flipping the nop jumps out the front, so only the loop can be fixed
+        let program = Console::from_lines(["nop -5", "jmp +0"].map(String::from))
+            .unwrap()
+            .program;
+        assert_eq!(Cfg::new(&program).terminating_flips(), [1]);
+    }
+
+    #[test]
+    fn unreachable_and_doomed() {
+        let cfg = test_cfg();
+        assert_eq!(cfg.unreachable(), [5, 8]);
+        assert_eq!(cfg.doomed(), [0, 1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn conditional_branches() {
+        let program =
+            Console::from_lines(["jz +3", "jmp +0", "acc +1", "nop +0"].map(String::from))
+                .unwrap()
+                .program;
+        let cfg = Cfg::new(&program);
+        assert_eq!(cfg.unreachable(), [2]);
+        assert_eq!(cfg.doomed(), [1]);
+        assert_eq!(cfg.terminating_flips(), [1]);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::{
+    instruction::{Code, Instruction, Registers},
+    machine::{Machine, Program, RunOutcome},
+};
+
+/// Where a jump lands, checked once at compile time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    To(usize),
+    /// Anywhere other than the program or just past its end
+    OutOfRange(isize),
+}
+
+/// An instruction decoded for the fast loop, with jumps resolved to absolute
+/// indices
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add {
+        reg: usize,
+        arg: isize,
+    },
+    Mul {
+        reg: usize,
+        arg: isize,
+    },
+    Set {
+        reg: usize,
+        arg: isize,
+    },
+    Jump(Target),
+    JumpIfZero {
+        reg: usize,
+        target: Target,
+    },
+    JumpIfNonZero {
+        reg: usize,
+        target: Target,
+    },
+    SkipIfEq {
+        reg: usize,
+        arg: isize,
+        target: Target,
+    },
+    SkipIfNe {
+        reg: usize,
+        arg: isize,
+        target: Target,
+    },
+    Next,
+}
+
+/// A program decoded ahead of time, for long runs where matching on `Code`
+/// every step adds up. Runs give the same `RunOutcome` as `Machine::run`.
+#[derive(Debug, Clone)]
+pub struct Compiled {
+    ops: Box<[Op]>,
+}
+
+impl Compiled {
+    /// Decodes `program` as `machine` sees it, including any patch
+    pub fn new(program: &Program, machine: &Machine) -> Self {
+        let len = program.len();
+        let target = |pc: usize, offset: isize| match pc.checked_add_signed(offset) {
+            Some(next) if next <= len => Target::To(next),
+            _ => Target::OutOfRange((pc as isize).saturating_add(offset)),
+        };
+
+        let ops = (0..len)
+            .filter_map(|pc| {
+                machine
+                    .fetch(program, pc)
+                    .map(|instruction| (pc, instruction))
+            })
+            .map(|(pc, Instruction { code, reg, arg })| match code {
+                Code::Acc => Op::Add { reg, arg },
+                Code::Mul => Op::Mul { reg, arg },
+                Code::Set => Op::Set { reg, arg },
+                Code::Jmp => match target(pc, arg) {
+                    Target::To(next) if next == pc + 1 => Op::Next,
+                    target => Op::Jump(target),
+                },
+                Code::Jz => Op::JumpIfZero {
+                    reg,
+                    target: target(pc, arg),
+                },
+                Code::Jnz => Op::JumpIfNonZero {
+                    reg,
+                    target: target(pc, arg),
+                },
+                Code::Seq => Op::SkipIfEq {
+                    reg,
+                    arg,
+                    target: target(pc, 2),
+                },
+                Code::Sne => Op::SkipIfNe {
+                    reg,
+                    arg,
+                    target: target(pc, 2),
+                },
+                Code::Nop => Op::Next,
+            })
+            .collect();
+        Self { ops }
+    }
+
+    /// Same as `Machine::run`, but over the decoded operations
+    pub fn run(&self, machine: &mut Machine, detect_loops: bool, max_steps: usize) -> RunOutcome {
+        let len = self.ops.len();
+        let mut pc = machine.pc;
+        let mut registers: Registers = machine.registers;
+        let mut steps = 0;
+
+        let jump = |pc: usize, target: Target| match target {
+            Target::To(next) => Ok(next),
+            Target::OutOfRange(target) => Err(RunOutcome::JumpedOutOfRange { pc, target }),
+        };
+
+        let outcome = loop {
+            if steps == max_steps {
+                break if pc == len {
+                    RunOutcome::Terminated(registers[0])
+                } else {
+                    RunOutcome::StepLimitExceeded
+                };
+            }
+            let Some(op) = self.ops.get(pc) else {
+                break RunOutcome::Terminated(registers[0]);
+            };
+            if detect_loops && machine.visited(pc) {
+                break RunOutcome::Looped {
+                    pc,
+                    acc: registers[0],
+                };
+            }
+            machine.mark_visited(pc);
+            steps += 1;
+
+            let next = match *op {
+                Op::Add { reg, arg } => {
+                    registers[reg] = registers[reg].wrapping_add(arg);
+                    Ok(pc + 1)
+                }
+                Op::Mul { reg, arg } => {
+                    registers[reg] = registers[reg].wrapping_mul(arg);
+                    Ok(pc + 1)
+                }
+                Op::Set { reg, arg } => {
+                    registers[reg] = arg;
+                    Ok(pc + 1)
+                }
+                Op::Jump(target) => jump(pc, target),
+                Op::JumpIfZero { reg, target } if registers[reg] == 0 => jump(pc, target),
+                Op::JumpIfNonZero { reg, target } if registers[reg] != 0 => jump(pc, target),
+                Op::SkipIfEq { reg, arg, target } if registers[reg] == arg => jump(pc, target),
+                Op::SkipIfNe { reg, arg, target } if registers[reg] != arg => jump(pc, target),
+                _ => Ok(pc + 1),
+            };
+            match next {
+                Ok(next) => pc = next,
+                Err(outcome) => break outcome,
+            }
+        };
+
+        machine.pc = pc;
+        machine.registers = registers;
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm, Console};
+
+    /// Runs both interpreters from fresh machines and checks they agree
+    fn assert_same(program: &Program, detect_loops: bool, max_steps: usize) -> RunOutcome {
+        let mut slow = Machine::new(program);
+        let mut fast = Machine::new(program);
+        let expected = slow.run(program, detect_loops, max_steps);
+        let outcome = Compiled::new(program, &fast).run(&mut fast, detect_loops, max_steps);
+        assert_eq!(outcome, expected);
+        assert_eq!(fast, slow);
+        outcome
+    }
+
+    #[test]
+    fn matches_machine() {
+        let program = Console::from_file("test-input").unwrap().program;
+        assert_eq!(
+            assert_same(&program, true, usize::MAX),
+            RunOutcome::Looped { pc: 1, acc: 5 }
+        );
+        assert_eq!(
+            assert_same(&program, false, 100),
+            RunOutcome::StepLimitExceeded
+        );
+
+        let sources: [&[&str]; 5] = [
+            &[
+                "set b +3", "acc +2", "acc b -1", "jnz b -2", "seq +6", "mul +2",
+            ],
+            &["acc +5", "jz +3", "seq +4", "mul -1", "nop +0"],
+            &["acc +1", "jmp -2"],
+            &["acc +1", "sne +1", "nop +0", "seq +1", "jmp +1"],
+            &["jmp +1", "jz +1", "jnz +0", "acc b +1", "sne b +1"],
+        ];
+        for source in sources {
+            let program = Program::from(asm::assemble(source).unwrap());
+            for max_steps in [0, 1, 3, 100] {
+                assert_same(&program, false, max_steps);
+                assert_same(&program, true, max_steps);
+            }
+        }
+    }
+
+    #[test]
+    fn uses_patch() {
+        let program = Console::from_file("test-input").unwrap().program;
+        let mut machine = Machine::new(&program);
+        machine.patch(7, "nop -4".parse().unwrap());
+        let compiled = Compiled::new(&program, &machine);
+        assert_eq!(
+            compiled.run(&mut machine, true, usize::MAX),
+            RunOutcome::Terminated(8)
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    fs::File,
+    io::{BufRead, BufWriter, Write},
+};
+
+use anyhow::{anyhow, bail};
+
+use crate::{
+    instruction::{Instruction, REGISTER_NAMES},
+    machine::Snapshot,
+    Console, RunOutcome,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Breakpoint {
+    /// Stop before running this instruction
+    Pc(usize),
+    /// Stop once the accumulator changes to this value
+    Acc(isize),
+}
+
+/// Why a `continue` handed control back
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Stop {
+    Breakpoint(Breakpoint),
+    /// About to run an instruction for the second time
+    Revisit(usize),
+    /// Can't go any further
+    Halted(RunOutcome),
+}
+
+/// One executed instruction
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TraceEntry {
+    pub pc: usize,
+    pub instruction: Instruction,
+    pub acc_before: isize,
+    pub acc_after: isize,
+}
+
+impl Display for TraceEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>5}  {:<10} acc {} -> {}",
+            self.pc,
+            self.instruction.to_string(),
+            self.acc_before,
+            self.acc_after
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct Debugger {
+    console: Console,
+    breakpoints: Vec<Breakpoint>,
+    /// Registers to print at every stop
+    watches: Vec<usize>,
+    /// Whether `continue` stops before repeating an instruction
+    break_on_revisit: bool,
+    trace: Vec<TraceEntry>,
+    /// Saved machine state, with the trace length at that point
+    checkpoint: Option<(Snapshot, usize)>,
+}
+
+impl Debugger {
+    pub fn new(console: Console) -> Self {
+        Self {
+            console,
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+            break_on_revisit: true,
+            trace: Vec::new(),
+            checkpoint: None,
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.retain(|b| *b != breakpoint);
+    }
+
+    pub fn watch(&mut self, reg: usize) {
+        if !self.watches.contains(&reg) {
+            self.watches.push(reg);
+        }
+    }
+
+    /// Runs a single instruction, or says why the program can't go on
+    pub fn step(&mut self) -> Result<TraceEntry, RunOutcome> {
+        let Console { program, machine } = &mut self.console;
+        let pc = machine.pc;
+        let acc_before = machine.accumulator();
+        let instruction = machine.fetch(program, pc);
+        machine.step(program)?;
+
+        let entry = TraceEntry {
+            pc,
+            instruction: instruction.unwrap(),
+            acc_before,
+            acc_after: machine.accumulator(),
+        };
+        self.trace.push(entry);
+        Ok(entry)
+    }
+
+    /// Runs at least one instruction, then keeps going until something to
+    /// stop for comes up
+    pub fn resume(&mut self) -> Stop {
+        loop {
+            let entry = match self.step() {
+                Ok(entry) => entry,
+                Err(outcome) => return Stop::Halted(outcome),
+            };
+            if let Some(stop) = self.check_stop(&entry) {
+                return stop;
+            }
+        }
+    }
+
+    fn check_stop(&self, last: &TraceEntry) -> Option<Stop> {
+        let Console { program, machine } = &self.console;
+        if machine.pc == program.len() {
+            return Some(Stop::Halted(RunOutcome::Terminated(last.acc_after)));
+        }
+        if self.break_on_revisit && machine.visited(machine.pc) {
+            return Some(Stop::Revisit(machine.pc));
+        }
+        self.breakpoints
+            .iter()
+            .find(|breakpoint| match breakpoint {
+                Breakpoint::Pc(pc) => *pc == machine.pc,
+                Breakpoint::Acc(acc) => *acc == last.acc_after && *acc != last.acc_before,
+            })
+            .map(|breakpoint| Stop::Breakpoint(*breakpoint))
+    }
+
+    pub fn save_trace(&self, name: &str) -> anyhow::Result<()> {
+        let mut out = BufWriter::new(File::create(name)?);
+        for entry in &self.trace {
+            writeln!(out, "{entry}")?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    fn print_watches<W: Write>(&self, out: &mut W) -> anyhow::Result<()> {
+        for reg in &self.watches {
+            writeln!(
+                out,
+                "  {} = {}",
+                REGISTER_NAMES[*reg], self.console.machine.registers[*reg]
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Reads commands from `input` until it ends or says `quit`:
+    ///
+    /// - `step [n]` / `s [n]`: run `n` (default 1) instructions, printing each
+    /// - `continue` / `c`: run until a breakpoint, a repeat or the end
+    /// - `break <pc>` / `break acc <value>`: add a breakpoint
+    /// - `delete <pc>` / `delete acc <value>`: remove a breakpoint
+    /// - `watch <register>`: print a register at every stop
+    /// - `loops on|off`: whether `continue` stops before repeating an instruction
+    /// - `trace`: print everything run so far
+    /// - `save <file>`: write everything run so far to a file
+    /// - `checkpoint`: remember the current state
+    /// - `rewind`: go back to the remembered state
+    pub fn repl<R: BufRead, W: Write>(&mut self, input: R, mut out: W) -> anyhow::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            let words: Vec<_> = line.split_whitespace().collect();
+            if let Err(e) = self.command(&words, &mut out) {
+                writeln!(out, "Error: {e}")?;
+            }
+            if matches!(words[..], ["quit"] | ["q"]) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn command<W: Write>(&mut self, words: &[&str], out: &mut W) -> anyhow::Result<()> {
+        let parse_breakpoint = |words: &[&str]| -> anyhow::Result<Breakpoint> {
+            match words {
+                [pc] => Ok(Breakpoint::Pc(pc.parse()?)),
+                ["acc", acc] => Ok(Breakpoint::Acc(acc.parse()?)),
+                _ => bail!("Expected `<pc>` or `acc <value>`"),
+            }
+        };
+
+        match words {
+            [] | ["quit"] | ["q"] => (),
+            ["step" | "s", count @ ..] => {
+                let count = match count {
+                    [] => 1,
+                    [count] => count.parse()?,
+                    _ => bail!("Expected `step [count]`"),
+                };
+                for _ in 0..count {
+                    match self.step() {
+                        Ok(entry) => writeln!(out, "{entry}")?,
+                        Err(outcome) => {
+                            writeln!(out, "{outcome}")?;
+                            break;
+                        }
+                    }
+                }
+                self.print_watches(out)?;
+            }
+            ["continue" | "c"] => {
+                let start = self.trace.len();
+                let stop = self.resume();
+                for entry in &self.trace[start..] {
+                    writeln!(out, "{entry}")?;
+                }
+                match stop {
+                    Stop::Breakpoint(Breakpoint::Pc(pc)) => writeln!(out, "Breakpoint at {pc}")?,
+                    Stop::Breakpoint(Breakpoint::Acc(acc)) => {
+                        writeln!(out, "Breakpoint on acc = {acc}")?
+                    }
+                    Stop::Revisit(pc) => writeln!(out, "About to repeat {pc}")?,
+                    Stop::Halted(outcome) => writeln!(out, "{outcome}")?,
+                }
+                self.print_watches(out)?;
+            }
+            ["break" | "b", rest @ ..] => self.add_breakpoint(parse_breakpoint(rest)?),
+            ["delete" | "d", rest @ ..] => self.remove_breakpoint(parse_breakpoint(rest)?),
+            ["watch" | "w", reg] => {
+                let reg = REGISTER_NAMES
+                    .iter()
+                    .position(|name| reg.len() == 1 && reg.starts_with(*name))
+                    .ok_or_else(|| anyhow!("No register named {reg}"))?;
+                self.watch(reg);
+            }
+            ["loops", "on"] => self.break_on_revisit = true,
+            ["loops", "off"] => self.break_on_revisit = false,
+            ["trace"] => {
+                for entry in &self.trace {
+                    writeln!(out, "{entry}")?;
+                }
+            }
+            ["save", name] => self.save_trace(name)?,
+            ["checkpoint"] => {
+                self.checkpoint = Some((self.console.machine.snapshot(), self.trace.len()))
+            }
+            ["rewind"] => {
+                let (snapshot, trace_len) = self
+                    .checkpoint
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("No checkpoint"))?;
+                self.console.machine.restore(snapshot);
+                self.trace.truncate(*trace_len);
+            }
+            _ => bail!("Unknown command: {}", words.join(" ")),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_debugger() -> Debugger {
+        Debugger::new(Console::from_file("test-input").unwrap())
+    }
+
+    #[test]
+    fn continue_to_repeat() {
+        let mut debugger = test_debugger();
+        assert_eq!(debugger.resume(), Stop::Revisit(1));
+        assert_eq!(debugger.console.machine.accumulator(), 5);
+        assert_eq!(debugger.trace.len(), 7);
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut debugger = test_debugger();
+        debugger.add_breakpoint(Breakpoint::Pc(4));
+        debugger.add_breakpoint(Breakpoint::Acc(2));
+        assert_eq!(debugger.resume(), Stop::Breakpoint(Breakpoint::Acc(2)));
+        assert_eq!(debugger.console.machine.pc, 7);
+        assert_eq!(debugger.resume(), Stop::Breakpoint(Breakpoint::Pc(4)));
+
+        debugger.remove_breakpoint(Breakpoint::Pc(4));
+        assert_eq!(debugger.resume(), Stop::Revisit(1));
+    }
+
+    #[test]
+    fn single_step() {
+        let mut debugger = test_debugger();
+        debugger.step().unwrap();
+        assert_eq!(
+            debugger.step(),
+            Ok(TraceEntry {
+                pc: 1,
+                instruction: "acc +1".parse().unwrap(),
+                acc_before: 0,
+                acc_after: 1,
+            })
+        );
+        assert_eq!(debugger.console.machine.pc, 2);
+    }
+
+    #[test]
+    fn halts() {
+        let console = Console::from_lines(["acc +2", "jmp -3"].map(String::from)).unwrap();
+        let mut debugger = Debugger::new(console);
+        assert_eq!(
+            debugger.resume(),
+            Stop::Halted(RunOutcome::JumpedOutOfRange { pc: 1, target: -2 })
+        );
+
+        let console = Console::from_lines(["acc +2"].map(String::from)).unwrap();
+        let mut debugger = Debugger::new(console);
+        assert_eq!(debugger.resume(), Stop::Halted(RunOutcome::Terminated(2)));
+        assert_eq!(debugger.step(), Err(RunOutcome::Terminated(2)));
+    }
+
+    #[test]
+    fn repl() {
+        let mut debugger = test_debugger();
+        let commands = "break 3\nwatch a\nc\ncheckpoint\ns 2\nbogus\nrewind\ns\nq\ns\n";
+        let mut out = Vec::new();
+        debugger.repl(commands.as_bytes(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "    0  nop +0     acc 0 -> 0
+    1  acc +1     acc 0 -> 1
+    2  jmp +4     acc 1 -> 1
+    6  acc +1     acc 1 -> 2
+    7  jmp -4     acc 2 -> 2
+Breakpoint at 3
+  a = 2
+    3  acc +3     acc 2 -> 5
+    4  jmp -3     acc 5 -> 5
+  a = 5
+Error: Unknown command: bogus
+    3  acc +3     acc 2 -> 5
+  a = 5
+"
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail};
+
+/// Register names, in register order. `a` is the accumulator.
+pub const REGISTER_NAMES: [char; 4] = ['a', 'b', 'c', 'd'];
+
+pub type Registers = [isize; REGISTER_NAMES.len()];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Code {
+    /// Add the argument to the register
+    Acc,
+    /// Multiply the register by the argument
+    Mul,
+    /// Overwrite the register with the argument
+    Set,
+    /// Jump by the argument
+    Jmp,
+    /// Jump by the argument if the register is zero
+    Jz,
+    /// Jump by the argument if the register is not zero
+    Jnz,
+    /// Skip the next instruction if the register equals the argument
+    Seq,
+    /// Skip the next instruction if the register differs from the argument
+    Sne,
+    Nop,
+}
+
+/// Mnemonic for each opcode
+const MNEMONICS: [(Code, &str); 9] = [
+    (Code::Acc, "acc"),
+    (Code::Mul, "mul"),
+    (Code::Set, "set"),
+    (Code::Jmp, "jmp"),
+    (Code::Jz, "jz"),
+    (Code::Jnz, "jnz"),
+    (Code::Seq, "seq"),
+    (Code::Sne, "sne"),
+    (Code::Nop, "nop"),
+];
+
+impl Code {
+    /// Runs the opcode against `reg`, returning the offset to the next instruction
+    pub fn execute(self, reg: &mut isize, arg: isize) -> isize {
+        let jump_if = |cond: bool| if cond { arg } else { 1 };
+        let skip_if = |cond: bool| if cond { 2 } else { 1 };
+        match self {
+            Self::Acc => {
+                *reg = reg.wrapping_add(arg);
+                1
+            }
+            Self::Mul => {
+                *reg = reg.wrapping_mul(arg);
+                1
+            }
+            Self::Set => {
+                *reg = arg;
+                1
+            }
+            Self::Jmp => arg,
+            Self::Jz => jump_if(*reg == 0),
+            Self::Jnz => jump_if(*reg != 0),
+            Self::Seq => skip_if(*reg == arg),
+            Self::Sne => skip_if(*reg != arg),
+            Self::Nop => 1,
+        }
+    }
+
+    /// Every offset `execute` could return, whatever the register holds
+    pub fn possible_offsets(self, arg: isize) -> Vec<isize> {
+        match self {
+            Self::Acc | Self::Mul | Self::Set | Self::Nop => vec![1],
+            Self::Jmp => vec![arg],
+            Self::Jz | Self::Jnz => vec![1, arg],
+            Self::Seq | Self::Sne => vec![1, 2],
+        }
+    }
+
+    /// The opcode that the corrupted instruction might have been
+    pub fn flipped(self) -> Option<Self> {
+        match self {
+            Self::Jmp => Some(Self::Nop),
+            Self::Nop => Some(Self::Jmp),
+            _ => None,
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        MNEMONICS
+            .iter()
+            .find(|(code, _)| *code == self)
+            .map(|(_, name)| *name)
+            .unwrap()
+    }
+}
+
+impl FromStr for Code {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MNEMONICS
+            .iter()
+            .find(|(_, name)| *name == s)
+            .map(|(code, _)| *code)
+            .ok_or(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Instruction {
+    pub code: Code,
+    /// Index into the registers
+    pub reg: usize,
+    pub arg: isize,
+}
+
+impl FromStr for Instruction {
+    type Err = anyhow::Error;
+
+    /// Parses `<code> [<register>] <argument>`, defaulting to the accumulator
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let words: Vec<_> = line.split(' ').collect();
+        let (code, reg, arg) = match words[..] {
+            [code, arg] => (code, None, arg),
+            [code, reg, arg] => (code, Some(reg), arg),
+            _ => bail!("Line does not match expected pattern: {}", line),
+        };
+
+        let code = Code::from_str(code).map_err(|_| anyhow!("Code malformed: {}", line))?;
+        let reg = match reg {
+            None => 0,
+            Some(reg) => REGISTER_NAMES
+                .iter()
+                .position(|name| reg.len() == 1 && reg.starts_with(*name))
+                .ok_or_else(|| anyhow!("Register malformed: {}", line))?,
+        };
+        let arg = arg
+            .parse::<isize>()
+            .map_err(|_| anyhow!("Argument number malformed: {}", line))?;
+
+        Ok(Self { code, reg, arg })
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code.mnemonic())?;
+        if self.reg != 0 {
+            write!(f, " {}", REGISTER_NAMES[self.reg])?;
+        }
+        write!(f, " {:+}", self.arg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_registers() {
+        assert_eq!(
+            Instruction::from_str("jnz c -2").unwrap(),
+            Instruction {
+                code: Code::Jnz,
+                reg: 2,
+                arg: -2
+            }
+        );
+        assert_eq!(
+            Instruction::from_str("acc +7").unwrap(),
+            Instruction {
+                code: Code::Acc,
+                reg: 0,
+                arg: 7
+            }
+        );
+        assert!(Instruction::from_str("acc e +1").is_err());
+        assert!(Instruction::from_str("div +1").is_err());
+        assert!(Instruction::from_str("acc +1 +1").is_err());
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for line in ["acc +1", "nop +0", "jmp -4", "mul b +3", "sne d -1"] {
+            assert_eq!(Instruction::from_str(line).unwrap().to_string(), line);
+        }
+    }
+}
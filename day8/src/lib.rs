@@ -0,0 +1,179 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+pub mod asm;
+pub mod cfg;
+pub mod compiled;
+pub mod debugger;
+pub mod instruction;
+pub mod machine;
+use cfg::Cfg;
+use compiled::Compiled;
+use machine::{Machine, Program, RunOutcome};
+
+/// A program together with the state of the machine running it
+#[derive(Debug, Clone)]
+pub struct Console {
+    pub program: Program,
+    pub machine: Machine,
+}
+
+impl Console {
+    pub fn from_file(name: &str) -> anyhow::Result<Self> {
+        let mut sentinel: anyhow::Result<()> = Ok(());
+        let lines = BufReader::new(File::open(name)?)
+            .lines()
+            .map_while(|line| match line {
+                Ok(line) => Some(line),
+                Err(e) => {
+                    sentinel = Err(e.into());
+                    None
+                }
+            });
+        let result = Self::from_lines(lines);
+        sentinel?;
+        result
+    }
+
+    pub fn from_lines<I>(lines: I) -> anyhow::Result<Self>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let program = Program::from(asm::assemble(lines)?);
+        let machine = Machine::new(&program);
+        Ok(Self { program, machine })
+    }
+}
+
+impl Console {
+    /// Runs until an instruction is about to repeat
+    pub fn accumulator_at_repeat(mut self) -> RunOutcome {
+        self.machine.run(&self.program, true, usize::MAX)
+    }
+
+    /// Flips the first `jmp`/`nop` that lets the program exit, then runs it.
+    /// Without such a flip this is the same as `accumulator_at_repeat`.
+    pub fn accumulator_with_fix(mut self) -> RunOutcome {
+        if let Some(idx) = Cfg::new(&self.program).terminating_flips().first() {
+            let mut instruction = self.program[*idx];
+            if let Some(flipped) = instruction.code.flipped() {
+                instruction.code = flipped;
+                self.machine.patch(*idx, instruction);
+            }
+        }
+        self.machine.run(&self.program, true, usize::MAX)
+    }
+
+    /// Runs until the end of the program without loop detection, which is
+    /// needed once conditional codes can revisit an instruction legitimately
+    pub fn accumulator_with_limit(mut self, max_steps: usize) -> RunOutcome {
+        self.machine.run(&self.program, false, max_steps)
+    }
+
+    /// Same as `accumulator_with_limit`, but decodes the program first
+    pub fn accumulator_compiled(mut self, max_steps: usize) -> RunOutcome {
+        Compiled::new(&self.program, &self.machine).run(&mut self.machine, false, max_steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::instruction::Instruction;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Console::from_file("test-input").unwrap().program[1],
+            Instruction::from_str("acc +1").unwrap()
+        );
+    }
+
+    #[test]
+    fn extended_codes() {
+        // b counts down from 3, adding 2 to a each time, then a is doubled
+        // unless it is 6
+        let lines = [
+            "set b +3", "acc +2", "acc b -1", "jnz b -2", "seq +6", "mul +2", "acc c +1",
+        ];
+        let console = Console::from_lines(lines.map(String::from)).unwrap();
+        assert_eq!(
+            console.accumulator_with_limit(100),
+            RunOutcome::Terminated(6)
+        );
+
+        let lines = ["acc +5", "jz +3", "seq +4", "mul -1", "nop +0"];
+        let console = Console::from_lines(lines.map(String::from)).unwrap();
+        assert_eq!(
+            console.accumulator_with_limit(100),
+            RunOutcome::Terminated(-5)
+        );
+
+        let lines = ["set b +1", "jnz b +0"];
+        let console = Console::from_lines(lines.map(String::from)).unwrap();
+        assert_eq!(
+            console.accumulator_with_limit(100),
+            RunOutcome::StepLimitExceeded
+        );
+    }
+
+    #[test]
+    fn out_of_range() {
+        for (lines, pc, target) in [
+            (["acc +1", "jmp -2", "nop +0"], 1, -1),
+            (["acc +1", "jmp +3", "nop +0"], 1, 4),
+            (["acc +1", "nop +0", "jnz +9"], 2, 11),
+        ] {
+            let console = Console::from_lines(lines.map(String::from)).unwrap();
+            assert_eq!(
+                console.clone().accumulator_at_repeat(),
+                RunOutcome::JumpedOutOfRange { pc, target }
+            );
+            assert_eq!(
+                console.accumulator_with_limit(10),
+                RunOutcome::JumpedOutOfRange { pc, target }
+            );
+        }
+
+        // Jumping to just past the end is a clean exit
+        let console = Console::from_lines(["jmp +2", "acc +1"].map(String::from)).unwrap();
+        assert_eq!(console.accumulator_at_repeat(), RunOutcome::Terminated(0));
+
+        let console = Console::from_lines(["jmp +0", "jmp +0"].map(String::from)).unwrap();
+        assert_eq!(
+            console.accumulator_with_fix(),
+            RunOutcome::Looped { pc: 0, acc: 0 }
+        );
+
+        // The fix mustn't send the program out the front
+        let console = Console::from_lines(["nop -5", "jmp +0"].map(String::from)).unwrap();
+        assert_eq!(console.accumulator_with_fix(), RunOutcome::Terminated(0));
+
+        let console = Console::from_lines(Vec::<String>::new()).unwrap();
+        assert_eq!(console.accumulator_at_repeat(), RunOutcome::Terminated(0));
+    }
+
+    #[test]
+    fn part1() {
+        assert_eq!(
+            Console::from_file("test-input")
+                .unwrap()
+                .accumulator_at_repeat(),
+            RunOutcome::Looped { pc: 1, acc: 5 }
+        )
+    }
+
+    #[test]
+    fn part2() {
+        assert_eq!(
+            Console::from_file("test-input")
+                .unwrap()
+                .accumulator_with_fix(),
+            RunOutcome::Terminated(8)
+        )
+    }
+}
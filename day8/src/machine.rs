@@ -0,0 +1,229 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    ops::Deref,
+    rc::Rc,
+};
+
+use crate::instruction::{Instruction, Registers};
+
+/// Immutable instructions, shared between every machine running them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program(Rc<[Instruction]>);
+
+impl From<Vec<Instruction>> for Program {
+    fn from(value: Vec<Instruction>) -> Self {
+        Self(value.into())
+    }
+}
+
+impl Deref for Program {
+    type Target = [Instruction];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// How a run of the console ended
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RunOutcome {
+    /// Ran off the end of the program, with the final accumulator
+    Terminated(isize),
+    /// Stopped before running `pc` for the second time
+    Looped {
+        pc: usize,
+        acc: isize,
+    },
+    /// The instruction at `pc` jumped somewhere other than the program or
+    /// just past its end
+    JumpedOutOfRange {
+        pc: usize,
+        target: isize,
+    },
+    StepLimitExceeded,
+}
+
+impl Display for RunOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Terminated(acc) => write!(f, "Terminated with acc {acc}"),
+            Self::Looped { pc, acc } => write!(f, "Looped at {pc} with acc {acc}"),
+            Self::JumpedOutOfRange { pc, target } => {
+                write!(f, "Jumped out of range from {pc} to {target}")
+            }
+            Self::StepLimitExceeded => write!(f, "Exceeded the step limit"),
+        }
+    }
+}
+
+/// Execution state for running a `Program`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Machine {
+    pub pc: usize,
+    pub registers: Registers,
+    /// One bit per instruction, set once it has run
+    visited: Vec<u64>,
+    /// Replaces one instruction of the program without copying it
+    patch: Option<(usize, Instruction)>,
+}
+
+/// Saved machine state, to go back to with `Machine::restore`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot(Machine);
+
+impl Machine {
+    pub fn new(program: &Program) -> Self {
+        Self {
+            pc: 0,
+            registers: Registers::default(),
+            visited: vec![0; program.len().div_ceil(64)],
+            patch: None,
+        }
+    }
+
+    /// Runs `instruction` in place of the one at `idx` from now on
+    pub fn patch(&mut self, idx: usize, instruction: Instruction) {
+        self.patch = Some((idx, instruction));
+    }
+
+    pub fn accumulator(&self) -> isize {
+        self.registers[0]
+    }
+
+    #[inline]
+    pub fn visited(&self, pc: usize) -> bool {
+        self.visited
+            .get(pc / 64)
+            .is_some_and(|word| word & (1 << (pc % 64)) != 0)
+    }
+
+    #[inline]
+    pub fn mark_visited(&mut self, pc: usize) {
+        if let Some(word) = self.visited.get_mut(pc / 64) {
+            *word |= 1 << (pc % 64);
+        }
+    }
+
+    /// The instruction at `pc`, with the patch applied
+    pub fn fetch(&self, program: &Program, pc: usize) -> Option<Instruction> {
+        match self.patch {
+            Some((idx, instruction)) if idx == pc && pc < program.len() => Some(instruction),
+            _ => program.get(pc).copied(),
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot(self.clone())
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.clone_from(&snapshot.0);
+    }
+
+    /// Runs the instruction at `pc`, or says how the program halted
+    pub fn step(&mut self, program: &Program) -> Result<(), RunOutcome> {
+        let pc = self.pc;
+        let Some(Instruction { code, reg, arg }) = self.fetch(program, pc) else {
+            return Err(RunOutcome::Terminated(self.accumulator()));
+        };
+        self.mark_visited(pc);
+        let offset = code.execute(&mut self.registers[reg], arg);
+
+        match pc.checked_add_signed(offset) {
+            Some(next) if next <= program.len() => {
+                self.pc = next;
+                Ok(())
+            }
+            _ => Err(RunOutcome::JumpedOutOfRange {
+                pc,
+                target: (pc as isize).saturating_add(offset),
+            }),
+        }
+    }
+
+    /// Runs for at most `max_steps` instructions, stopping before any
+    /// repeated instruction if `detect_loops` is set
+    pub fn run(&mut self, program: &Program, detect_loops: bool, max_steps: usize) -> RunOutcome {
+        for _ in 0..max_steps {
+            if detect_loops && self.visited(self.pc) {
+                return RunOutcome::Looped {
+                    pc: self.pc,
+                    acc: self.accumulator(),
+                };
+            }
+            if let Err(outcome) = self.step(program) {
+                return outcome;
+            }
+        }
+
+        if self.pc == program.len() {
+            RunOutcome::Terminated(self.accumulator())
+        } else {
+            RunOutcome::StepLimitExceeded
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    fn program(lines: &[&str]) -> Program {
+        asm::assemble(lines).unwrap().into()
+    }
+
+    #[test]
+    fn snapshot_restore() {
+        let program = program(&["acc +1", "acc +2", "acc +4", "jmp -3"]);
+        let mut machine = Machine::new(&program);
+        machine.step(&program).unwrap();
+        let snapshot = machine.snapshot();
+
+        assert_eq!(
+            machine.run(&program, true, usize::MAX),
+            RunOutcome::Looped { pc: 0, acc: 7 }
+        );
+        machine.restore(&snapshot);
+        assert_eq!(machine.pc, 1);
+        assert_eq!(machine.accumulator(), 1);
+        assert!(machine.visited(0));
+        assert!(!machine.visited(1));
+        assert_eq!(
+            machine.run(&program, true, usize::MAX),
+            RunOutcome::Looped { pc: 0, acc: 7 }
+        );
+    }
+
+    #[test]
+    fn shared_patches() {
+        let program = program(&["acc +1", "jmp -1", "acc +2"]);
+        let mut patched = Machine::new(&program);
+        patched.patch(1, "nop +0".parse().unwrap());
+        let runs: Vec<_> = [Machine::new(&program), patched]
+            .into_iter()
+            .map(|mut machine| machine.run(&program.clone(), true, usize::MAX))
+            .collect();
+        assert_eq!(
+            runs,
+            [
+                RunOutcome::Looped { pc: 0, acc: 1 },
+                RunOutcome::Terminated(3)
+            ]
+        );
+        assert_eq!(program[1], "jmp -1".parse().unwrap());
+    }
+
+    #[test]
+    fn visited_bits() {
+        let lines = vec!["nop +0"; 200];
+        let program = program(&lines);
+        let mut machine = Machine::new(&program);
+        assert_eq!(
+            machine.run(&program, true, 130),
+            RunOutcome::StepLimitExceeded
+        );
+        assert!((0..130).all(|pc| machine.visited(pc)));
+        assert!((130..200).all(|pc| !machine.visited(pc)));
+    }
+}
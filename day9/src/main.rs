@@ -1,103 +1,343 @@
 use std::{
-    cmp::Ordering,
-    collections::VecDeque,
+    collections::HashMap,
+    env,
+    error::Error,
     fs::File,
-    io::{BufRead, BufReader},
+    io::{self, BufRead, BufReader},
+    str::FromStr,
 };
 
 use anyhow::anyhow;
-use itertools::Itertools;
-
-fn main() {
-    let invalid = Xmas::new(file_to_i32("input"), 25).invalid().unwrap();
-    println!("{}", invalid);
-    let mut sequence = Xmas::new(file_to_i32("input"), 25)
-        .series_sum(invalid)
-        .unwrap();
-    sequence.sort();
-    println!("{}", sequence.first().unwrap() + sequence.last().unwrap());
+use num_bigint::BigInt;
+use window::{Number, PairWindow};
+
+mod window;
+
+const PREAMBLE: usize = 25;
+
+/// Usage: `day9 [all|watch]`
+///
+/// - `all` lists every invalid number and every range summing to the first one
+/// - `watch` reads numbers from stdin, printing each invalid one as it arrives
+fn main() -> anyhow::Result<()> {
+    let args: Vec<_> = env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["watch"] => {
+            let xmas = Xmas::<BigInt>::from_reader(io::stdin().lock(), PREAMBLE)?;
+            for check in xmas.invalids() {
+                let check = check?;
+                println!("Invalid: {} at {}", check.value, check.position);
+            }
+        }
+        ["all"] => {
+            for check in Xmas::<BigInt>::from_file("input", PREAMBLE)?.invalids() {
+                let check = check?;
+                println!("Invalid: {} at {}", check.value, check.position);
+            }
+            let invalid = Xmas::<BigInt>::from_file("input", PREAMBLE)?.invalid()?;
+            for range in Xmas::from_file("input", PREAMBLE)?.ranges_summing_to(invalid) {
+                let range = range?;
+                println!(
+                    "Range {}..={}: min {}, max {}",
+                    range.start, range.end, range.min, range.max
+                );
+            }
+        }
+        _ => {
+            let invalid = Xmas::<BigInt>::from_file("input", PREAMBLE)?.invalid()?;
+            println!("{}", invalid);
+            let range = Xmas::from_file("input", PREAMBLE)?
+                .ranges_summing_to(invalid)
+                .next()
+                .ok_or_else(|| anyhow!("No range sums to the invalid number"))??;
+            println!("{}", range.min + range.max);
+        }
+    }
+    Ok(())
+}
+
+type Numbers<T> = Box<dyn Iterator<Item = anyhow::Result<T>>>;
+
+struct Xmas<T> {
+    window: PairWindow<T>,
+    /// How many numbers went into the preamble
+    preamble: usize,
+    it: Numbers<T>,
 }
 
-struct Xmas {
-    numbers: VecDeque<i32>,
-    it: Box<dyn Iterator<Item = i32>>,
+/// A number after the preamble, with how many pairs in the window before it
+/// sum to it
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Check<T> {
+    /// Index in the whole stream, counting the preamble
+    position: usize,
+    value: T,
+    pairs: usize,
 }
 
-impl Xmas {
-    fn new<I>(value: I, count: usize) -> Self
+/// Contiguous numbers from `start` to `end` inclusive, by stream index
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct SumRange<T> {
+    start: usize,
+    end: usize,
+    min: T,
+    max: T,
+}
+
+impl<T: Number + 'static> Xmas<T> {
+    /// Reads the preamble straight away, and the rest only as it's checked
+    fn new<I>(value: I, count: usize) -> anyhow::Result<Self>
     where
-        I: Iterator<Item = i32> + 'static,
+        I: Iterator<Item = anyhow::Result<T>> + 'static,
     {
         let mut it = value;
-        Xmas {
-            numbers: it.by_ref().take(count).collect(),
-            it: Box::new(it),
+        let mut window = PairWindow::new(count);
+        let mut preamble = 0;
+        for value in it.by_ref().take(count) {
+            window.push(value?);
+            preamble += 1;
         }
+        Ok(Xmas {
+            window,
+            preamble,
+            it: Box::new(it),
+        })
+    }
+
+    /// One number per line, from anything buffered, such as a file or stdin
+    fn from_reader<R>(reader: R, count: usize) -> anyhow::Result<Self>
+    where
+        R: BufRead + 'static,
+        T: FromStr,
+        T::Err: Error + Send + Sync + 'static,
+    {
+        let numbers = reader.lines().enumerate().map(|(idx, line)| {
+            let line = line?;
+            line.trim()
+                .parse()
+                .map_err(|e| anyhow!("Line {}: {e}: \"{line}\"", idx + 1))
+        });
+        Self::new(numbers, count)
+    }
+
+    fn from_file(name: &str, count: usize) -> anyhow::Result<Self>
+    where
+        T: FromStr,
+        T::Err: Error + Send + Sync + 'static,
+    {
+        Self::from_reader(BufReader::new(File::open(name)?), count)
     }
 }
 
-impl Xmas {
-    fn invalid(mut self) -> anyhow::Result<i32> {
-        for entry in self.it {
-            let prev: Vec<_> = self.numbers.iter().sorted().cloned().collect();
-            let mut found = false;
-            for val in &prev[..prev.len() - 1] {
-                if prev.binary_search(&(entry - val)).is_ok() {
-                    self.numbers.pop_front();
-                    self.numbers.push_back(entry);
-                    found = true;
-                    break;
-                }
-            }
-            if !found {
-                return Ok(entry);
-            }
-        }
-        Err(anyhow!("The exchange is fully valid"))
+impl<T: Number + 'static> Xmas<T> {
+    /// Checks every number after the preamble against the window before it,
+    /// as soon as it's read
+    fn checks(self) -> impl Iterator<Item = anyhow::Result<Check<T>>> {
+        let Xmas {
+            mut window,
+            preamble,
+            it,
+        } = self;
+        it.enumerate().map(move |(idx, value)| {
+            let value = value?;
+            let pairs = window.pairs_summing_to(&value);
+            window.push(value.clone());
+            Ok(Check {
+                position: preamble + idx,
+                value,
+                pairs,
+            })
+        })
     }
 
-    fn series_sum(self, target: i32) -> anyhow::Result<Vec<i32>> {
-        let mut num_it = self.numbers.iter().copied();
-        let mut set: VecDeque<i32> = num_it.by_ref().take(2).collect();
-        let mut full_it = num_it.chain(self.it);
+    /// Every number that isn't the sum of a pair in the window before it
+    fn invalids(self) -> impl Iterator<Item = anyhow::Result<Check<T>>> {
+        self.checks()
+            .filter(|check| !matches!(check, Ok(check) if check.pairs > 0))
+    }
 
-        // Guaranteed to end as long as iterator is finite
-        loop {
-            match set.iter().sum::<i32>().cmp(&target) {
-                Ordering::Equal => return Ok(Vec::from(set)),
-                Ordering::Greater => {
-                    set.pop_front();
-                }
-                Ordering::Less => set.push_back(match full_it.next() {
-                    Some(x) => x,
-                    None => return Err(anyhow!("Exhausted iterator with no matches")),
-                }),
-            }
-        }
+    fn invalid(self) -> anyhow::Result<T> {
+        self.invalids()
+            .next()
+            .ok_or_else(|| anyhow!("The exchange is fully valid"))?
+            .map(|check| check.value)
     }
-}
 
-fn file_to_i32(name: &str) -> impl Iterator<Item = i32> {
-    BufReader::new(File::open(name).unwrap())
-        .lines()
-        .map(|line| line.unwrap().parse::<i32>().unwrap())
+    /// Every run of at least two numbers anywhere in the stream, preamble
+    /// included, that sums to `target`. Ranges come out by end, then by start.
+    ///
+    /// Works on prefix sums, so negative numbers are fine too.
+    fn ranges_summing_to(self, target: T) -> impl Iterator<Item = anyhow::Result<SumRange<T>>> {
+        let target: BigInt = target.into();
+        let mut values: Vec<T> = Vec::new();
+        // Indexes where the sum of everything before them had each value
+        let mut starts: HashMap<BigInt, Vec<usize>> = HashMap::new();
+        let mut prefix = BigInt::default();
+
+        let numbers: Vec<_> = self.window.iter().cloned().map(Ok).collect();
+        numbers.into_iter().chain(self.it).flat_map(move |value| {
+            let value = match value {
+                Ok(value) => value,
+                Err(e) => return vec![Err(e)],
+            };
+            let end = values.len();
+            values.push(value.clone());
+            starts.entry(prefix.clone()).or_default().push(end);
+            prefix += value.into();
+
+            starts
+                .get(&(&prefix - &target))
+                .into_iter()
+                .flatten()
+                .filter(|start| **start < end)
+                .map(|start| {
+                    let range = &values[*start..=end];
+                    Ok(SumRange {
+                        start: *start,
+                        end,
+                        min: range.iter().min().unwrap().clone(),
+                        max: range.iter().max().unwrap().clone(),
+                    })
+                })
+                .collect()
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn test_xmas() -> Xmas<i32> {
+        Xmas::from_file("test-input", 5).unwrap()
+    }
+
+    fn xmas<T, I>(numbers: I, preamble: usize) -> Xmas<T>
+    where
+        T: Number + 'static,
+        I: IntoIterator<Item = T>,
+        I::IntoIter: 'static,
+    {
+        Xmas::new(numbers.into_iter().map(Ok), preamble).unwrap()
+    }
+
     #[test]
     fn part1() {
-        let it = file_to_i32("test-input");
-        let data = Xmas::new(it, 5);
-        assert_eq!(data.invalid().unwrap(), 127);
+        assert_eq!(test_xmas().invalid().unwrap(), 127);
+    }
+
+    #[test]
+    fn pair_counts() {
+        let checks: Vec<_> = test_xmas().checks().collect::<anyhow::Result<_>>().unwrap();
+        assert_eq!(checks.len(), 15);
+        assert_eq!(
+            checks[0],
+            Check {
+                position: 5,
+                value: 40,
+                pairs: 1
+            }
+        );
+        assert_eq!(
+            checks[9],
+            Check {
+                position: 14,
+                value: 127,
+                pairs: 0
+            }
+        );
+        assert_eq!(checks.iter().filter(|check| check.pairs == 0).count(), 1);
+    }
+
+    #[test]
+    fn invalid_positions() {
+        let numbers = [1, 2, 3, 10, 5, 13, 20, 9];
+        let invalids: Vec<_> = xmas(numbers, 3)
+            .invalids()
+            .map(|check| check.map(|check| (check.position, check.value)).unwrap())
+            .collect();
+        assert_eq!(invalids, [(3, 10), (6, 20), (7, 9)]);
     }
 
     #[test]
     fn part2() {
-        let weakness = Xmas::new(file_to_i32("test-input"), 5).invalid().unwrap();
-        let data = Xmas::new(file_to_i32("test-input"), 5);
-        assert_eq!(data.series_sum(weakness).unwrap(), [15, 25, 47, 40]);
+        let weakness = test_xmas().invalid().unwrap();
+        let ranges: Vec<_> = test_xmas()
+            .ranges_summing_to(weakness)
+            .collect::<anyhow::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            ranges,
+            [SumRange {
+                start: 2,
+                end: 5,
+                min: 15,
+                max: 47
+            }]
+        );
+    }
+
+    #[test]
+    fn all_ranges() {
+        let numbers = [3, 1, 2, -1, 4, 3, 0, 6];
+        let ranges: Vec<_> = xmas(numbers, 2)
+            .ranges_summing_to(6)
+            .collect::<anyhow::Result<_>>()
+            .unwrap();
+
+        let mut expected = Vec::new();
+        for end in 0..numbers.len() {
+            for start in 0..end {
+                let range = &numbers[start..=end];
+                if range.iter().sum::<i32>() == 6 {
+                    expected.push(SumRange {
+                        start,
+                        end,
+                        min: *range.iter().min().unwrap(),
+                        max: *range.iter().max().unwrap(),
+                    });
+                }
+            }
+        }
+        assert_eq!(ranges, expected);
+        // Starts inside the preamble, which the old window missed
+        assert_eq!(ranges[0].start, 0);
+    }
+
+    #[test]
+    fn wide_numbers() {
+        let big = i128::from(i64::MAX);
+        let numbers = [big, big + 2, 1, big + 3, 2 * big + 2, 5];
+        let xmas = xmas(numbers, 3);
+        assert_eq!(xmas.invalid().unwrap(), 2 * big + 2);
+
+        let input = "170141183460469231731687303715884105727
+170141183460469231731687303715884105728
+1
+340282366920938463463374607431768211455
+";
+        let xmas = Xmas::<BigInt>::from_reader(input.as_bytes(), 2).unwrap();
+        let invalid = xmas.invalid().unwrap();
+        assert_eq!(invalid.to_string(), "1");
+        let range = Xmas::<BigInt>::from_reader(input.as_bytes(), 2)
+            .unwrap()
+            .ranges_summing_to("340282366920938463463374607431768211456".parse().unwrap())
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!((range.start, range.end), (0, 2));
+    }
+
+    #[test]
+    fn streaming_errors() {
+        let mut invalids = Xmas::<u8>::from_reader("1\n2\n3\n10\n256\n".as_bytes(), 3)
+            .unwrap()
+            .invalids();
+        assert_eq!(invalids.next().unwrap().unwrap().value, 10);
+        let e = invalids.next().unwrap().unwrap_err();
+        assert!(e.to_string().starts_with("Line 5:"));
+
+        assert!(Xmas::<u8>::from_reader("1\nx\n3\n".as_bytes(), 3).is_err());
+        assert!(Xmas::<u8>::from_file("no-such-file", 3).is_err());
     }
 }
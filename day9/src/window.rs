@@ -0,0 +1,116 @@
+use std::{
+    collections::{hash_map::Entry, HashMap, VecDeque},
+    fmt::{Debug, Display},
+    hash::Hash,
+};
+
+use num_bigint::BigInt;
+use num_traits::CheckedSub;
+
+/// Any integer `Xmas` can check, from `u8` up to `BigInt`. Sums of ranges are
+/// taken as `BigInt` so they can't overflow.
+pub trait Number: Clone + Ord + Hash + Debug + Display + CheckedSub + Into<BigInt> {}
+
+impl<T> Number for T where T: Clone + Ord + Hash + Debug + Display + CheckedSub + Into<BigInt> {}
+
+/// The last `size` numbers of a stream, with a count of each value so that
+/// checking for pairs costs one lookup per distinct value instead of a sort
+#[derive(Debug, Clone)]
+pub struct PairWindow<T> {
+    size: usize,
+    order: VecDeque<T>,
+    counts: HashMap<T, usize>,
+}
+
+impl<T: Number> PairWindow<T> {
+    pub fn new(size: usize) -> Self {
+        Self {
+            size,
+            order: VecDeque::with_capacity(size),
+            counts: HashMap::with_capacity(size),
+        }
+    }
+
+    /// Adds `value`, dropping the oldest number once the window is full
+    pub fn push(&mut self, value: T) {
+        if self.order.len() == self.size {
+            if let Some(oldest) = self.order.pop_front() {
+                if let Entry::Occupied(mut entry) = self.counts.entry(oldest) {
+                    *entry.get_mut() -= 1;
+                    if *entry.get() == 0 {
+                        entry.remove();
+                    }
+                }
+            }
+        }
+        if self.size > 0 {
+            *self.counts.entry(value.clone()).or_default() += 1;
+            self.order.push_back(value);
+        }
+    }
+
+    /// How many pairs of numbers in the window, with different values, sum
+    /// to `target`
+    pub fn pairs_summing_to(&self, target: &T) -> usize {
+        self.counts
+            .iter()
+            .filter_map(|(value, count)| {
+                let other = target.checked_sub(value)?;
+                (*value < other).then(|| count * self.counts.get(&other).unwrap_or(&0))
+            })
+            .sum()
+    }
+
+    /// Oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.order.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_pairs() {
+        let mut window = PairWindow::new(4);
+        for value in [1, 2, 2, 3, 4] {
+            window.push(value);
+        }
+        assert_eq!(window.iter().collect::<Vec<_>>(), [&2, &2, &3, &4]);
+        assert_eq!(window.pairs_summing_to(&5), 2);
+        assert_eq!(window.pairs_summing_to(&6), 2);
+        assert_eq!(window.pairs_summing_to(&7), 1);
+        assert_eq!(window.pairs_summing_to(&4), 0);
+        assert_eq!(window.pairs_summing_to(&i32::MIN), 0);
+    }
+
+    #[test]
+    fn large_window() {
+        let size = 5000;
+        let mut window = PairWindow::new(size);
+        for value in 0..20_000 {
+            window.push(value);
+        }
+        // 15000..20000, and the pairs from 15000 + 19999 inward
+        assert_eq!(window.pairs_summing_to(&34_999), size / 2);
+        assert_eq!(window.pairs_summing_to(&(15_000 + 15_001)), 1);
+        assert_eq!(window.pairs_summing_to(&(14_999 + 15_000)), 0);
+    }
+
+    #[test]
+    fn wide_types() {
+        let mut window = PairWindow::new(3);
+        for value in [u128::MAX - 1, 1, 0] {
+            window.push(value);
+        }
+        assert_eq!(window.pairs_summing_to(&u128::MAX), 1);
+        assert_eq!(window.pairs_summing_to(&1), 1);
+
+        let big: BigInt = "100000000000000000000000000000".parse().unwrap();
+        let mut window = PairWindow::new(2);
+        window.push(big.clone());
+        window.push(big.clone() + 1);
+        assert_eq!(window.pairs_summing_to(&(big * 2 + 1)), 1);
+    }
+}